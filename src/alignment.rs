@@ -0,0 +1,204 @@
+// Relative alignment of two IMUs on the same rigid body
+//
+// Both IMUs see the same angular velocity and, when standing still, the same gravity
+// vector, only expressed in different frames. Pairs of these vectors are matched by time
+// and the rotation is found by solving Wahba's problem (SVD, as in the Kabsch algorithm).
+//
+// refs:
+// https://en.wikipedia.org/wiki/Wahba%27s_problem
+
+use crate::cal::Cal;
+use crate::data_provider::SampleInfo;
+use nalgebra::{Matrix3, Rotation3, Vector3};
+
+const MAX_TIME_DIFF: f64 = 5e-3; // s, max. time difference of paired samples
+const MIN_ANG_VEL: f64 = 0.2; // rad/s, gyro vectors below are dominated by bias and noise
+const G0: f64 = 9.80665;
+const MAX_ACC_DEVIATION: f64 = 0.5; // m/s², from G0 to count as static
+const MIN_DIRECTION_SPREAD: f64 = 0.01; // second / first singular value, about 11° between directions
+
+#[derive(Debug, Clone)]
+pub struct Alignment {
+    pub rotation: Rotation3<f64>, // from the frame of the second IMU to the first one
+    pub residual: f64,            // rad, RMS angle between the paired vectors
+    pub gyro_pairs: usize,
+    pub acc_pairs: usize,
+}
+
+impl Alignment {
+    /// Roll, pitch, yaw in degrees.
+    pub fn euler_angles_deg(&self) -> Vector3<f64> {
+        let (roll, pitch, yaw) = self.rotation.euler_angles();
+        Vector3::new(roll.to_degrees(), pitch.to_degrees(), yaw.to_degrees())
+    }
+}
+
+fn sample_time(info: &SampleInfo) -> Option<f64> {
+    // the host clock is shared by all devices, log imports only have the device clock
+    info.host_time.or(info.device_time)
+}
+
+/// Pairs each sample of `a` with the closest sample of `b` in time. Both are expected to be
+/// in chronological order.
+pub fn pair_by_time(
+    a: &[Vector3<f64>],
+    a_info: &[SampleInfo],
+    b: &[Vector3<f64>],
+    b_info: &[SampleInfo],
+) -> Vec<(Vector3<f64>, Vector3<f64>)> {
+    let b_times: Vec<_> = b_info.iter().map(sample_time).collect();
+    let mut pairs = vec![];
+    let mut j = 0;
+
+    for (va, info) in a.iter().zip(a_info) {
+        let Some(ta) = sample_time(info) else {
+            continue;
+        };
+        let dt = |j: usize| b_times[j].map_or(f64::INFINITY, |tb| (tb - ta).abs());
+
+        while j + 1 < b_times.len() && dt(j + 1) <= dt(j) {
+            j += 1;
+        }
+        if j < b.len() && dt(j) < MAX_TIME_DIFF {
+            pairs.push((*va, b[j]));
+        }
+    }
+
+    pairs
+}
+
+/// Rotation R minimizing the angles between `a` and `R * b` of the given unit vector pairs.
+/// Needs at least two clearly different directions, the rotation about a single one is
+/// undetermined.
+pub fn solve_wahba(pairs: &[(Vector3<f64>, Vector3<f64>)]) -> Option<Rotation3<f64>> {
+    if pairs.len() < 2 {
+        return None;
+    }
+
+    let mut b = Matrix3::zeros();
+    for (va, vb) in pairs {
+        b += va * vb.transpose();
+    }
+
+    let svd = b.svd(true, true);
+    let mut sigma: Vec<_> = svd.singular_values.iter().copied().collect();
+    sigma.sort_by(|a, b| b.total_cmp(a));
+    if sigma[1] < MIN_DIRECTION_SPREAD * sigma[0] {
+        return None;
+    }
+    let u = svd.u?;
+    let v_t = svd.v_t?;
+    let d = Matrix3::from_diagonal(&Vector3::new(
+        1.0,
+        1.0,
+        (u.determinant() * v_t.determinant()).signum(),
+    ));
+
+    Some(Rotation3::from_matrix_unchecked(u * d * v_t))
+}
+
+/// Rotation between the frames of two IMUs, from gyro vectors during motion and accel
+/// vectors while standing still. Returns `None` if there are not enough simultaneous
+/// samples or they all point in about the same direction.
+pub fn align(first: &Cal, second: &Cal) -> Option<Alignment> {
+    let gyro_pairs: Vec<_> = pair_by_time(
+        first.gyro_measurements(),
+        first.gyro_info(),
+        second.gyro_measurements(),
+        second.gyro_info(),
+    )
+    .into_iter()
+    .filter(|(a, b)| a.norm() > MIN_ANG_VEL && b.norm() > MIN_ANG_VEL)
+    .collect();
+
+    let acc_pairs: Vec<_> = pair_by_time(
+        first.acc_measurements(),
+        first.acc_info(),
+        second.acc_measurements(),
+        second.acc_info(),
+    )
+    .into_iter()
+    .filter(|(a, b)| {
+        (a.norm() - G0).abs() < MAX_ACC_DEVIATION && (b.norm() - G0).abs() < MAX_ACC_DEVIATION
+    })
+    .collect();
+
+    // unit vectors, so rotations and gravity are weighted alike
+    let pairs: Vec<_> = gyro_pairs
+        .iter()
+        .chain(acc_pairs.iter())
+        .map(|(a, b)| (a.normalize(), b.normalize()))
+        .collect();
+
+    let rotation = solve_wahba(&pairs)?;
+    let residual = (pairs
+        .iter()
+        .map(|(a, b)| {
+            let b = rotation * b;
+            a.cross(&b).norm().atan2(a.dot(&b)).powi(2) // acos is inaccurate near 0
+        })
+        .sum::<f64>()
+        / pairs.len() as f64)
+        .sqrt();
+
+    Some(Alignment {
+        rotation,
+        residual,
+        gyro_pairs: gyro_pairs.len(),
+        acc_pairs: acc_pairs.len(),
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn rotation_between_imus() {
+        let rotation = Rotation3::from_euler_angles(0.1, -0.4, 2.0);
+        let mut first = Cal::new();
+        let mut second = Cal::new();
+
+        for i in 0..200 {
+            let t = i as f64 * 0.01;
+            let ang_vel = Vector3::new(t.sin(), (2.0 * t).cos(), 0.5 + t * 0.1);
+            let acc = Rotation3::from_euler_angles(t, 0.3 * t, 0.0) * Vector3::new(0.0, 0.0, G0);
+            let info = |dt: f64| SampleInfo {
+                host_time: Some(t + dt),
+                ..Default::default()
+            };
+
+            first.add_gyro_measurement(ang_vel, info(0.0));
+            first.add_acc_measurement(acc, info(0.0));
+            second.add_gyro_measurement(rotation.inverse() * ang_vel, info(1e-3));
+            second.add_acc_measurement(rotation.inverse() * acc, info(1e-3));
+        }
+
+        let alignment = align(&first, &second).unwrap();
+        assert_eq!(alignment.gyro_pairs, 200);
+        assert_eq!(alignment.acc_pairs, 200);
+        assert!((alignment.rotation.matrix() - rotation.matrix()).norm() < 1e-9);
+        assert!(alignment.residual < 1e-9);
+    }
+
+    #[test]
+    fn single_pose() {
+        // both IMUs resting in one orientation, only the gravity direction is paired
+        let rotation = Rotation3::from_euler_angles(0.1, -0.4, 2.0);
+        let mut first = Cal::new();
+        let mut second = Cal::new();
+        let acc = Vector3::new(0.3, -0.2, G0);
+        for i in 0..200 {
+            let info = SampleInfo {
+                host_time: Some(i as f64 * 0.01),
+                ..Default::default()
+            };
+            first.add_gyro_measurement(Vector3::zeros(), info);
+            first.add_acc_measurement(acc, info);
+            second.add_gyro_measurement(Vector3::zeros(), info);
+            second.add_acc_measurement(rotation.inverse() * acc, info);
+        }
+
+        assert!(align(&first, &second).is_none());
+    }
+}
@@ -0,0 +1,195 @@
+// Allan deviation of a static recording
+//
+// The overlapping Allan deviation is computed from the integrated signal (angle or
+// velocity) for log-spaced cluster times. The noise terms are read off the log-log curve
+// where it has the characteristic slope:
+//
+//   random walk (N)        slope -1/2, value at tau = 1 s
+//   bias instability (B)   slope 0, minimum / sqrt(2 ln 2 / pi)
+//   rate random walk (K)   slope +1/2, value at tau = 3 s
+//
+// refs:
+// IEEE Std 952-1997, Annex C
+// https://www.mathworks.com/help/nav/ug/inertial-sensor-noise-analysis-using-allan-variance.html
+
+use crate::data_provider::SampleInfo;
+use crate::gyro_cal;
+use nalgebra::Vector3;
+
+const TAUS_PER_DECADE: f64 = 10.0;
+const MIN_CLUSTERS: usize = 9; // the estimate gets too noisy above tau = T / 9
+const SLOPE_TOLERANCE: f64 = 0.15; // of the local slope to count as a noise term
+const BIAS_INSTABILITY_FACTOR: f64 = 0.664; // sqrt(2 ln 2 / pi)
+
+/// Noise terms of one axis, in the units of the samples (e.g. rad/s).
+#[derive(Debug, Clone, Copy, Default, serde::Serialize)]
+pub struct NoiseParams {
+    pub random_walk: Option<f64>, // N, per √Hz (angle/velocity random walk)
+    pub bias_instability: Option<f64>, // B
+    pub rate_random_walk: Option<f64>, // K, ·√Hz
+}
+
+#[derive(Debug, Clone)]
+pub struct AllanAnalysis {
+    pub period: f64,    // s, sample period
+    pub taus: Vec<f64>, // s, cluster times
+    pub adev: [Vec<f64>; 3],
+    pub params: [NoiseParams; 3],
+}
+
+/// Median interval of the sample timestamps.
+pub fn sample_period(info: &[SampleInfo]) -> Option<f64> {
+    let times = gyro_cal::sample_times(info)?;
+    let mut intervals: Vec<_> = times
+        .windows(2)
+        .map(|w| w[1] - w[0])
+        .filter(|dt| *dt > 0.0)
+        .collect();
+    if intervals.is_empty() {
+        return None;
+    }
+    intervals.sort_by(|a, b| a.total_cmp(b));
+    Some(intervals[intervals.len() / 2])
+}
+
+/// Log-spaced cluster sizes in samples.
+fn cluster_sizes(n: usize) -> Vec<usize> {
+    let max = n / MIN_CLUSTERS;
+    let mut sizes: Vec<usize> = vec![];
+    let mut i = 0.0;
+    loop {
+        let m = 10f64.powf(i / TAUS_PER_DECADE).round() as usize;
+        if m > max {
+            break;
+        }
+        if sizes.last() != Some(&m) {
+            sizes.push(m);
+        }
+        i += 1.0;
+    }
+    sizes
+}
+
+/// Overlapping Allan deviation of `samples` taken every `period` seconds, for the given
+/// cluster sizes.
+pub fn allan_deviation(samples: &[f64], period: f64, sizes: &[usize]) -> Vec<f64> {
+    // integrated signal
+    let mut theta = Vec::with_capacity(samples.len() + 1);
+    theta.push(0.0);
+    for s in samples {
+        theta.push(theta.last().unwrap() + s * period);
+    }
+
+    sizes
+        .iter()
+        .map(|&m| {
+            let tau = m as f64 * period;
+            let count = theta.len() - 2 * m;
+            let sum: f64 = (0..count)
+                .map(|k| (theta[k + 2 * m] - 2.0 * theta[k + m] + theta[k]).powi(2))
+                .sum();
+            (sum / (2.0 * tau * tau * count as f64)).sqrt()
+        })
+        .collect()
+}
+
+/// Value of the line with the given log-log slope through the point of the curve whose
+/// local slope is closest to it, evaluated at `tau_eval`.
+fn line_at(taus: &[f64], adev: &[f64], slope: f64, tau_eval: f64) -> Option<f64> {
+    // flat or quantized recordings have zero deviations, their slope is undefined
+    let (i, local) = (0..taus.len().saturating_sub(1))
+        .map(|i| {
+            let local = (adev[i + 1] / adev[i]).log10() / (taus[i + 1] / taus[i]).log10();
+            (i, local)
+        })
+        .filter(|(_, local)| local.is_finite())
+        .min_by(|a, b| (a.1 - slope).abs().total_cmp(&(b.1 - slope).abs()))?;
+    if (local - slope).abs() > SLOPE_TOLERANCE {
+        return None;
+    }
+    Some(adev[i] * (tau_eval / taus[i]).powf(slope))
+}
+
+pub fn noise_params(taus: &[f64], adev: &[f64]) -> NoiseParams {
+    NoiseParams {
+        random_walk: line_at(taus, adev, -0.5, 1.0),
+        bias_instability: line_at(taus, adev, 0.0, 1.0)
+            .map(|_| adev.iter().copied().fold(f64::INFINITY, f64::min) / BIAS_INSTABILITY_FACTOR),
+        rate_random_walk: line_at(taus, adev, 0.5, 3.0),
+    }
+}
+
+/// Allan analysis of all three axes. Needs timestamped samples.
+pub fn analyze(points: &[Vector3<f64>], info: &[SampleInfo]) -> Option<AllanAnalysis> {
+    let period = sample_period(info)?;
+    let sizes = cluster_sizes(points.len());
+    if sizes.len() < 2 {
+        return None;
+    }
+
+    let adev = [0, 1, 2].map(|axis| {
+        let samples: Vec<_> = points.iter().map(|p| p[axis]).collect();
+        allan_deviation(&samples, period, &sizes)
+    });
+    let taus: Vec<_> = sizes.iter().map(|m| *m as f64 * period).collect();
+    let params = [0, 1, 2].map(|axis| noise_params(&taus, &adev[axis]));
+
+    Some(AllanAnalysis {
+        period,
+        taus,
+        adev,
+        params,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn white_noise() {
+        // white noise with density 0.01 /√Hz at 100 Hz, gaussian by Box-Muller
+        let period: f64 = 0.01;
+        let density = 0.01;
+        let sigma = density / period.sqrt();
+        let mut seed = 1u64;
+        let mut uniform = || {
+            seed = seed
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            ((seed >> 11) as f64 + 0.5) / (1u64 << 53) as f64
+        };
+
+        let mut points = vec![];
+        let mut info = vec![];
+        for i in 0..100_000 {
+            let n = (-2.0 * uniform().ln()).sqrt() * (2.0 * std::f64::consts::PI * uniform()).cos();
+            points.push(Vector3::new(n, -n, 0.5 * n) * sigma);
+            info.push(SampleInfo {
+                device_time: Some(i as f64 * period),
+                ..Default::default()
+            });
+        }
+
+        let analysis = analyze(&points, &info).unwrap();
+        assert!((analysis.period - period).abs() < 1e-9);
+        for (axis, scale) in [1.0, 1.0, 0.5].iter().enumerate() {
+            let n = analysis.params[axis].random_walk.unwrap();
+            assert!((n / (density * scale) - 1.0).abs() < 0.05, "{n}");
+        }
+    }
+
+    #[test]
+    fn flat_recording() {
+        let points = vec![Vector3::new(0.01, 0.0, -0.02); 1000];
+        let info: Vec<_> = (0..1000)
+            .map(|i| SampleInfo {
+                device_time: Some(i as f64 * 0.01),
+                ..Default::default()
+            })
+            .collect();
+
+        let analysis = analyze(&points, &info).unwrap();
+        assert!(analysis.params[0].random_walk.is_none());
+    }
+}
@@ -0,0 +1,176 @@
+use nalgebra::{Matrix3, Vector3};
+
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
+pub enum Axis {
+    PosX,
+    NegX,
+    PosY,
+    NegY,
+    PosZ,
+    NegZ,
+}
+
+impl Axis {
+    pub const ALL: [Axis; 6] = [
+        Axis::PosX,
+        Axis::NegX,
+        Axis::PosY,
+        Axis::NegY,
+        Axis::PosZ,
+        Axis::NegZ,
+    ];
+
+    pub fn index(&self) -> usize {
+        match self {
+            Axis::PosX | Axis::NegX => 0,
+            Axis::PosY | Axis::NegY => 1,
+            Axis::PosZ | Axis::NegZ => 2,
+        }
+    }
+
+    pub fn sign(&self) -> f64 {
+        match self {
+            Axis::PosX | Axis::PosY | Axis::PosZ => 1.0,
+            Axis::NegX | Axis::NegY | Axis::NegZ => -1.0,
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Axis::PosX => "+X",
+            Axis::NegX => "−X",
+            Axis::PosY => "+Y",
+            Axis::NegY => "−Y",
+            Axis::PosZ => "+Z",
+            Axis::NegZ => "−Z",
+        }
+    }
+
+    pub fn unit_vector(&self) -> Vector3<f64> {
+        let mut v = Vector3::zeros();
+        v[self.index()] = self.sign();
+        v
+    }
+}
+
+/// Maps the axes of a sensor onto the common body frame. Each output axis takes a sensor
+/// axis, e.g. `[+Y, +X, −Z]` for the AK8963 inside the MPU-9250.
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct AxisMap {
+    pub axes: [Axis; 3],
+}
+
+impl Default for AxisMap {
+    fn default() -> Self {
+        Self {
+            axes: [Axis::PosX, Axis::PosY, Axis::PosZ],
+        }
+    }
+}
+
+impl AxisMap {
+    pub const fn new(x: Axis, y: Axis, z: Axis) -> Self {
+        Self { axes: [x, y, z] }
+    }
+
+    /// The 24 right-angle orientations, i.e. all signed permutations forming a rotation.
+    pub fn presets() -> Vec<AxisMap> {
+        let mut presets = vec![];
+        for x in Axis::ALL {
+            for y in Axis::ALL {
+                for z in Axis::ALL {
+                    let map = AxisMap::new(x, y, z);
+                    if map.is_permutation() && map.is_proper() {
+                        presets.push(map);
+                    }
+                }
+            }
+        }
+        presets
+    }
+
+    pub fn matrix(&self) -> Matrix3<f64> {
+        Matrix3::from_rows(&[
+            self.axes[0].unit_vector().transpose(),
+            self.axes[1].unit_vector().transpose(),
+            self.axes[2].unit_vector().transpose(),
+        ])
+    }
+
+    pub fn apply(&self, v: Vector3<f64>) -> Vector3<f64> {
+        Vector3::new(
+            v[self.axes[0].index()] * self.axes[0].sign(),
+            v[self.axes[1].index()] * self.axes[1].sign(),
+            v[self.axes[2].index()] * self.axes[2].sign(),
+        )
+    }
+
+    pub fn is_permutation(&self) -> bool {
+        let [x, y, z] = self.axes.map(|a| a.index());
+        x != y && y != z && x != z
+    }
+
+    /// A proper map is a rotation, an improper one mirrors the frame (left-handed sensor).
+    pub fn is_proper(&self) -> bool {
+        self.matrix().determinant() > 0.0
+    }
+
+    /// Assigns a sensor axis to an output axis. The output axis that used the same sensor
+    /// axis before takes over the freed one, keeping its sign, so the map stays a
+    /// permutation.
+    pub fn set(&mut self, output: usize, axis: Axis) {
+        let freed = self.axes[output].index();
+        if let Some(other) = (0..3).find(|i| *i != output && self.axes[*i].index() == axis.index())
+        {
+            let negative = self.axes[other].sign() < 0.0;
+            self.axes[other] = Axis::ALL[freed * 2 + negative as usize];
+        }
+        self.axes[output] = axis;
+    }
+
+    pub fn label(&self) -> String {
+        format!(
+            "{} {} {}",
+            self.axes[0].label(),
+            self.axes[1].label(),
+            self.axes[2].label()
+        )
+    }
+}
+
+/// Axis maps of the three sensors, applied between the providers and the calibration.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct AxisMaps {
+    pub gyro: AxisMap,
+    pub acc: AxisMap,
+    pub mag: AxisMap,
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn presets() {
+        let presets = AxisMap::presets();
+        assert_eq!(presets.len(), 24);
+        assert!(presets.contains(&AxisMap::new(Axis::PosY, Axis::PosX, Axis::NegZ)));
+
+        let map = AxisMap::new(Axis::PosY, Axis::PosX, Axis::NegZ);
+        let v = Vector3::new(1.0, 2.0, 3.0);
+        assert_eq!(map.apply(v), map.matrix() * v);
+        assert_eq!(map.apply(v), Vector3::new(2.0, 1.0, -3.0));
+    }
+
+    #[test]
+    fn set_keeps_permutation() {
+        let mut map = AxisMap::default();
+        map.set(0, Axis::PosY);
+        assert_eq!(map, AxisMap::new(Axis::PosY, Axis::PosX, Axis::PosZ));
+        assert!(map.is_permutation());
+        assert!(!map.is_proper()); // swapping two axes mirrors the frame
+
+        map.set(2, Axis::NegZ);
+        assert!(map.is_proper());
+    }
+}
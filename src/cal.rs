@@ -1,25 +1,89 @@
 const G0: f64 = 9.80665;
 const G0_THR: f64 = G0 * 0.75;
 const F0: f64 = 48.8819; // uT
+const STILL_ACC_TOLERANCE: f64 = 0.02; // relative deviation of |a| from g to count as still
+const MIN_SPHERE_CONDITION: f64 = 0.01; // smallest / largest singular value of the sphere fit
+const MIN_MAG_SPREAD: f64 = 0.05; // smallest / largest variance of the mag points for the ellipsoid fit
 
-use nalgebra::{Dyn, Matrix3, Vector3, U10};
+use nalgebra::{DMatrix, DVector, Dyn, Matrix3, Vector3, U10};
 use std::fs::File;
 use std::io::prelude::*;
+use std::ops::Range;
 use std::path::PathBuf;
 
+use crate::axis_map::{Axis, AxisMaps};
+use crate::data_provider::SampleInfo;
+use crate::earth;
+use crate::gyro_cal::{self, GyroModel};
+use crate::log_import::SensorKind;
+use crate::poses::{self, Pose};
 use crate::quality::Quality;
+use crate::temp_comp::{self, TempCal, TempCurve};
+use crate::wmm::{self, MagField, Wmm};
 
 #[derive(Debug, Clone, serde::Serialize)]
 pub struct CalData {
     pub gyro_offset: Vector3<f64>,
+    pub gyro_transf: nalgebra::Matrix3<f64>, // scale and misalignment: G * (w - gyro_offset)
+    // rotation part of gyro_transf (gyro into accel frame), identity unless the gyro was
+    // calibrated against the accel
+    pub gyro_acc_rotation: nalgebra::Matrix3<f64>,
     pub acc_offset: Vector3<f64>,
-    pub acc_scale: Vector3<f64>,
+    pub acc_scale: Vector3<f64>, // diagonal of acc_transf, for users of the per-axis model
+    pub acc_transf: nalgebra::Matrix3<f64>, // scale and misalignment: T * (a - acc_offset)
+    pub acc_residual: f64,       // m/s², RMS of |a| - g of the static poses after calibration
+    pub gravity: f64,            // m/s², g the accel is scaled to
     pub soft_iron_transf: nalgebra::Matrix3<f64>,
     pub hard_iron_bias: Vector3<f64>,
     pub mag_quality: Quality,
+    // applied to the raw sensor axes before any of the above
+    pub gyro_axis_map: nalgebra::Matrix3<f64>,
+    pub acc_axis_map: nalgebra::Matrix3<f64>,
+    pub mag_axis_map: nalgebra::Matrix3<f64>,
+    // drift removed from the raw values before any of the above
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub temp_cal: Option<TempCal>,
+    // gyro bias change per m/s² of specific force, (rad/s) / (m/s²)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub gyro_g_sensitivity: Option<Matrix3<f64>>,
+    // Earth rate in the accel frame, removed from the gyro offset
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub earth_rate: Option<Vector3<f64>>,
+    pub mag_model: MagModel, // model actually fitted, never Auto
+    pub mag_scale: MagScale,
+    pub mag_field: f64, // magnitude the soft iron transform scales to, 1 on the unit sphere
+    // rad, from the World Magnetic Model
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mag_declination: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mag_inclination_expected: Option<f64>,
+    // rad, of the calibrated mag while the accel was still
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mag_inclination: Option<f64>,
 }
 
 impl CalData {
+    pub fn apply_gyro_cal(&self, gyro_point: &Vector3<f64>) -> Vector3<f64> {
+        self.gyro_transf * (gyro_point - self.gyro_offset)
+    }
+
+    /// Gyro calibration including the g-sensitivity, `acc` is the calibrated accel sample
+    /// taken at the same time.
+    pub fn apply_gyro_cal_with_acc(
+        &self,
+        gyro_point: &Vector3<f64>,
+        acc: &Vector3<f64>,
+    ) -> Vector3<f64> {
+        match self.gyro_g_sensitivity {
+            Some(s) => self.gyro_transf * (gyro_point - self.gyro_offset - s * acc),
+            None => self.apply_gyro_cal(gyro_point),
+        }
+    }
+
+    pub fn apply_acc_cal(&self, acc_point: &Vector3<f64>) -> Vector3<f64> {
+        self.acc_transf * (acc_point - self.acc_offset)
+    }
+
     pub fn apply_mag_cal(&self, mag_point: &Vector3<f64>) -> Vector3<f64> {
         self.soft_iron_transf * (mag_point - self.hard_iron_bias)
     }
@@ -30,16 +94,211 @@ impl CalData {
     }
 }
 
+/// Removes the temperature drift, samples without temperature are passed through.
+fn compensate(
+    points: &[Vector3<f64>],
+    info: &[SampleInfo],
+    temp_cal: &Option<TempCal>,
+    curve: impl Fn(&TempCal) -> &Option<TempCurve>,
+) -> Vec<Vector3<f64>> {
+    let Some((temp_cal, curve)) = temp_cal
+        .as_ref()
+        .and_then(|t| Some((t, curve(t).as_ref()?)))
+    else {
+        return points.to_vec();
+    };
+    points
+        .iter()
+        .zip(info)
+        .map(|(p, i)| match i.temperature {
+            Some(t) => curve.compensate(p, t, temp_cal.t_ref),
+            None => *p,
+        })
+        .collect()
+}
+
+/// Index of the latest `b` sample at the time of each `a` sample. Without timestamps the
+/// indices are spread evenly.
+fn latest_samples(a: &[SampleInfo], b: &[SampleInfo]) -> Vec<usize> {
+    if b.is_empty() {
+        return vec![];
+    }
+    match (gyro_cal::sample_times(a), gyro_cal::sample_times(b)) {
+        (Some(a_times), Some(b_times)) => a_times
+            .iter()
+            .map(|t| b_times.partition_point(|tb| tb <= t).saturating_sub(1))
+            .collect(),
+        _ => (0..a.len()).map(|i| i * b.len() / a.len()).collect(),
+    }
+}
+
+/// Ratio of the smallest to the largest variance of the points along the principal axes,
+/// close to zero if they were rotated about one axis only.
+fn mag_spread(points: &[Vector3<f64>]) -> f64 {
+    if points.len() < 2 {
+        return 0.0;
+    }
+    let mean = points.iter().sum::<Vector3<f64>>() / points.len() as f64;
+    let cov = points
+        .iter()
+        .map(|p| (p - mean) * (p - mean).transpose())
+        .sum::<Matrix3<f64>>()
+        / points.len() as f64;
+    let variances = cov.symmetric_eigenvalues();
+    if variances.max() <= 0.0 {
+        return 0.0;
+    }
+    variances.min() / variances.max()
+}
+
+/// Samples taken within the time span, all of them if not timestamped.
+fn samples_during(
+    points: &[Vector3<f64>],
+    info: &[SampleInfo],
+    span: Option<(f64, f64)>,
+) -> Vec<Vector3<f64>> {
+    match (span, gyro_cal::sample_times(info)) {
+        (Some((start, end)), Some(times)) => points
+            .iter()
+            .zip(times)
+            .filter(|(_, t)| (start..=end).contains(t))
+            .map(|(p, _)| *p)
+            .collect(),
+        _ => points.to_vec(),
+    }
+}
+
+/// Mean of the samples taken within the time span, all of them if not timestamped.
+fn mean_during(
+    points: &[Vector3<f64>],
+    info: &[SampleInfo],
+    span: Option<(f64, f64)>,
+) -> Option<Vector3<f64>> {
+    let selected = samples_during(points, info, span);
+    (!selected.is_empty()).then(|| selected.iter().sum::<Vector3<f64>>() / selected.len() as f64)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AccModel {
+    SixFace,   // per axis offset and scale from the faces pointing up and down
+    Ellipsoid, // offset and full correction matrix, fitted to the static poses
+}
+
+impl AccModel {
+    pub const ALL: [AccModel; 2] = [AccModel::SixFace, AccModel::Ellipsoid];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            AccModel::SixFace => "Six faces",
+            AccModel::Ellipsoid => "Ellipsoid (misalignment)",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MagModel {
+    Auto,      // ellipsoid, sphere if the rotations don't cover enough of it
+    Ellipsoid, // hard iron offset and soft iron matrix
+    Sphere,    // hard iron offset only, for partial rotations
+}
+
+impl MagModel {
+    pub const ALL: [MagModel; 3] = [MagModel::Auto, MagModel::Ellipsoid, MagModel::Sphere];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            MagModel::Auto => "Auto",
+            MagModel::Ellipsoid => "Ellipsoid (soft iron)",
+            MagModel::Sphere => "Sphere (hard iron only)",
+        }
+    }
+}
+
+/// Scale of the calibrated mag output.
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MagScale {
+    Field,      // µT, the magnitude of the expected field
+    UnitSphere, // unitless, magnitude 1, for filters using only the direction
+}
+
+impl MagScale {
+    pub const ALL: [MagScale; 2] = [MagScale::Field, MagScale::UnitSphere];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            MagScale::Field => "Field (µT)",
+            MagScale::UnitSphere => "Unit sphere",
+        }
+    }
+
+    pub fn unit(&self) -> &'static str {
+        match self {
+            MagScale::Field => "µT",
+            MagScale::UnitSphere => "1",
+        }
+    }
+}
+
+/// File format of saved measurements. The sample info was added later and is
+/// missing in older files.
+#[derive(Debug, Default, serde::Serialize, serde::Deserialize)]
+struct Recording {
+    acc: Vec<Vector3<f64>>,
+    gyro: Vec<Vector3<f64>>,
+    mag: Vec<Vector3<f64>>,
+    #[serde(default)]
+    acc_info: Vec<SampleInfo>,
+    #[serde(default)]
+    gyro_info: Vec<SampleInfo>,
+    #[serde(default)]
+    mag_info: Vec<SampleInfo>,
+}
+
 #[derive(Debug)]
 pub struct Cal {
     gyro_points: Vec<Vector3<f64>>,
     acc_points: Vec<Vector3<f64>>,
     mag_points: Vec<Vector3<f64>>,
 
+    // timing and temperature of each point above
+    gyro_info: Vec<SampleInfo>,
+    acc_info: Vec<SampleInfo>,
+    mag_info: Vec<SampleInfo>,
+
     acc_points_avg: Vector3<f64>,
     gyro_points_avg: Vector3<f64>,
 
     cal_data: Option<CalData>,
+    temp_problem: Option<String>, // why the last calibration fitted no temperature drift
+
+    pub axis_maps: AxisMaps, // the points above are already mapped, kept for the results
+
+    // max. deviation from the moving average to count as standing still
+    pub acc_still_threshold: f64,  // m/s²
+    pub gyro_still_threshold: f64, // rad/s
+
+    pub acc_model: AccModel,
+    pub gyro_model: GyroModel,
+    pub gyro_g_sensitivity: bool,
+    pub gyro_turns: f64,     // per rotation, GyroModel::KnownTurns
+    pub turntable_rate: f64, // rad/s, GyroModel::Turntable
+
+    pub temp_comp: bool,
+    pub temp_degree: usize, // of the drift polynomials
+    pub temp_scale: bool,   // fit the scale drift of accel and mag as well
+
+    pub earth_rate: bool,    // remove the Earth rate from the gyro offset
+    pub local_gravity: bool, // scale the accel to the normal gravity instead of G0
+    pub latitude: f64,       // rad
+    pub height: f64,         // m, above the WGS84 ellipsoid
+    pub declination: f64,    // rad, positive east
+    pub mag_model: MagModel,
+    pub mag_scale: MagScale,
+    pub wmm: bool,      // expected mag field from the World Magnetic Model
+    pub longitude: f64, // rad
+    pub year: f64,      // decimal year, for the WMM
 }
 
 impl Cal {
@@ -48,17 +307,46 @@ impl Cal {
             gyro_points: vec![],
             acc_points: vec![],
             mag_points: vec![],
+            gyro_info: vec![],
+            acc_info: vec![],
+            mag_info: vec![],
             acc_points_avg: Default::default(),
             gyro_points_avg: Default::default(),
             cal_data: None,
+            temp_problem: None,
+            axis_maps: Default::default(),
+            acc_still_threshold: 1e-2,
+            gyro_still_threshold: 1e-3,
+            acc_model: AccModel::SixFace,
+            gyro_model: GyroModel::Offset,
+            gyro_g_sensitivity: false,
+            gyro_turns: 1.0,
+            turntable_rate: 90f64.to_radians(),
+            temp_comp: false,
+            temp_degree: 2,
+            temp_scale: false,
+            earth_rate: false,
+            local_gravity: false,
+            latitude: 0.0,
+            height: 0.0,
+            mag_model: MagModel::Auto,
+            mag_scale: MagScale::Field,
+            wmm: false,
+            longitude: 0.0,
+            year: wmm::current_year(),
+            declination: 0.0,
         }
     }
 
     pub fn save_to_file(&self, path: PathBuf) {
-        let mut data = std::collections::HashMap::new();
-        data.insert("acc", self.acc_points.clone());
-        data.insert("gyro", self.gyro_points.clone());
-        data.insert("mag", self.mag_points.clone());
+        let data = Recording {
+            acc: self.acc_points.clone(),
+            gyro: self.gyro_points.clone(),
+            mag: self.mag_points.clone(),
+            acc_info: self.acc_info.clone(),
+            gyro_info: self.gyro_info.clone(),
+            mag_info: self.mag_info.clone(),
+        };
 
         let json_string = serde_json::to_string(&data).unwrap();
 
@@ -71,42 +359,51 @@ impl Cal {
         let mut json_string = String::new();
         file.read_to_string(&mut json_string).unwrap();
 
-        let data: std::collections::HashMap<&str, Vec<Vector3<f64>>> =
-            serde_json::de::from_str(&json_string).unwrap();
-
-        self.acc_points.extend_from_slice(&data["acc"]);
-        self.gyro_points.extend_from_slice(&data["gyro"]);
-        self.mag_points.extend_from_slice(&data["mag"]);
+        let mut data: Recording = serde_json::de::from_str(&json_string).unwrap();
+        data.acc_info.resize(data.acc.len(), SampleInfo::default());
+        data.gyro_info
+            .resize(data.gyro.len(), SampleInfo::default());
+        data.mag_info.resize(data.mag.len(), SampleInfo::default());
+
+        self.acc_points.extend_from_slice(&data.acc);
+        self.gyro_points.extend_from_slice(&data.gyro);
+        self.mag_points.extend_from_slice(&data.mag);
+        self.acc_info.extend_from_slice(&data.acc_info);
+        self.gyro_info.extend_from_slice(&data.gyro_info);
+        self.mag_info.extend_from_slice(&data.mag_info);
     }
 
-    pub fn add_acc_measurement_still(&mut self, data: Vector3<f64>) {
+    pub fn add_acc_measurement_still(&mut self, data: Vector3<f64>, info: SampleInfo) {
         let alpha = 0.95;
         self.acc_points_avg = self.acc_points_avg * alpha + data * (1.0 - alpha);
 
-        if (self.acc_points_avg - data).norm() < 1e-2 {
-            self.acc_points.push(data);
+        if (self.acc_points_avg - data).norm() < self.acc_still_threshold {
+            self.add_acc_measurement(data, info);
         }
     }
 
-    pub fn add_gyro_measurement_still(&mut self, data: Vector3<f64>) {
+    pub fn add_gyro_measurement_still(&mut self, data: Vector3<f64>, info: SampleInfo) {
         let alpha = 0.98;
         self.gyro_points_avg = self.gyro_points_avg * alpha + data * (1.0 - alpha);
 
-        if (self.gyro_points_avg - data).norm() < 1e-3 {
-            self.gyro_points.push(data);
+        if (self.gyro_points_avg - data).norm() < self.gyro_still_threshold {
+            self.add_gyro_measurement(data, info);
         }
     }
 
-    pub fn add_gyro_measurement(&mut self, data: Vector3<f64>) {
+    pub fn add_gyro_measurement(&mut self, data: Vector3<f64>, info: SampleInfo) {
         self.gyro_points.push(data);
+        self.gyro_info.push(info);
     }
 
-    pub fn add_acc_measurement(&mut self, data: Vector3<f64>) {
+    pub fn add_acc_measurement(&mut self, data: Vector3<f64>, info: SampleInfo) {
         self.acc_points.push(data);
+        self.acc_info.push(info);
     }
 
-    pub fn add_mag_measurement(&mut self, data: Vector3<f64>) {
+    pub fn add_mag_measurement(&mut self, data: Vector3<f64>, info: SampleInfo) {
         self.mag_points.push(data);
+        self.mag_info.push(info);
     }
 
     pub fn gyro_measurements(&self) -> &Vec<Vector3<f64>> {
@@ -119,10 +416,28 @@ impl Cal {
 
     pub fn gyro_measurements_with_cal(&self) -> Vec<Vector3<f64>> {
         if let Some(cal_data) = self.cal_data.as_ref() {
-            self.gyro_points
-                .iter()
-                .map(|p| *p - cal_data.gyro_offset)
-                .collect::<Vec<_>>()
+            let (acc, acc_index) = match cal_data.gyro_g_sensitivity {
+                Some(_) => (
+                    self.acc_measurements_with_cal(),
+                    latest_samples(&self.gyro_info, &self.acc_info),
+                ),
+                None => (vec![], vec![]),
+            };
+            compensate(
+                &self.gyro_points,
+                &self.gyro_info,
+                &cal_data.temp_cal,
+                |t| &t.gyro,
+            )
+            .iter()
+            .enumerate()
+            .map(|(i, p)| match cal_data.gyro_g_sensitivity {
+                Some(_) if !acc.is_empty() => {
+                    cal_data.apply_gyro_cal_with_acc(p, &acc[acc_index[i]])
+                }
+                _ => cal_data.apply_gyro_cal(p),
+            })
+            .collect::<Vec<_>>()
         } else {
             vec![]
         }
@@ -130,10 +445,12 @@ impl Cal {
 
     pub fn acc_measurements_with_cal(&self) -> Vec<Vector3<f64>> {
         if let Some(cal_data) = self.cal_data.as_ref() {
-            self.acc_points
-                .iter()
-                .map(|p| (*p - cal_data.acc_offset).component_mul(&cal_data.acc_scale))
-                .collect::<Vec<_>>()
+            compensate(&self.acc_points, &self.acc_info, &cal_data.temp_cal, |t| {
+                &t.acc
+            })
+            .iter()
+            .map(|p| cal_data.apply_acc_cal(p))
+            .collect::<Vec<_>>()
         } else {
             vec![]
         }
@@ -141,10 +458,12 @@ impl Cal {
 
     pub fn mag_measurements_with_cal(&self) -> Vec<Vector3<f64>> {
         if let Some(cal_data) = self.cal_data.as_ref() {
-            self.mag_points
-                .iter()
-                .map(|p| cal_data.apply_mag_cal(p))
-                .collect::<Vec<_>>()
+            compensate(&self.mag_points, &self.mag_info, &cal_data.temp_cal, |t| {
+                &t.mag
+            })
+            .iter()
+            .map(|p| cal_data.apply_mag_cal(p))
+            .collect::<Vec<_>>()
         } else {
             vec![]
         }
@@ -154,148 +473,591 @@ impl Cal {
         &self.mag_points
     }
 
+    /// Faces the six-face method has no samples of, their axes stay uncalibrated.
+    pub fn missing_acc_faces(&self) -> Vec<Axis> {
+        Axis::ALL
+            .into_iter()
+            .filter(|face| {
+                !self
+                    .acc_points
+                    .iter()
+                    .any(|p| p[face.index()] * face.sign() > G0_THR)
+            })
+            .collect()
+    }
+
+    /// Still periods of the accel samples, averaged to one vector each.
+    pub fn acc_poses(&self) -> Vec<Pose> {
+        poses::segment_static(&self.acc_points)
+    }
+
+    /// Gravity the accel is scaled to, m/s².
+    pub fn gravity(&self) -> f64 {
+        if self.local_gravity {
+            earth::normal_gravity(self.latitude, self.height)
+        } else {
+            G0
+        }
+    }
+
+    /// Expected mag field at the location and date, if the WMM is enabled.
+    pub fn expected_field(&self) -> Option<MagField> {
+        self.wmm
+            .then(|| Wmm::wmm2025().field(self.latitude, self.longitude, self.height, self.year))
+    }
+
+    /// Declination in rad, from the WMM if enabled, the entered one otherwise.
+    pub fn magnetic_declination(&self) -> f64 {
+        self.expected_field()
+            .map_or(self.declination, |f| f.declination())
+    }
+
+    /// Headings of the body x axis (rad) from gyrocompassing and, if there are mag samples,
+    /// from the mag. Uses the calibration if it has the Earth rate removed, the raw gyro
+    /// samples are only good enough for tactical grade gyros otherwise.
+    pub fn gyrocompass(&self) -> Option<(f64, Option<f64>)> {
+        let mean = |points: &[Vector3<f64>]| {
+            (!points.is_empty()).then(|| points.iter().sum::<Vector3<f64>>() / points.len() as f64)
+        };
+        let (gyro, acc, mag) = match self.cal_data.as_ref() {
+            Some(cal_data) if cal_data.earth_rate.is_some() => (
+                mean(&self.gyro_measurements_with_cal())?,
+                mean(&self.acc_measurements_with_cal())?,
+                mean(&self.mag_measurements_with_cal()),
+            ),
+            _ => (
+                mean(&self.gyro_points)?,
+                mean(&self.acc_points)?,
+                mean(&self.mag_points),
+            ),
+        };
+        let heading = earth::gyrocompass(&gyro, &acc)?;
+        let mag_heading =
+            mag.and_then(|mag| earth::mag_heading(&acc, &mag, self.magnetic_declination()));
+        Some((heading, mag_heading))
+    }
+
+    pub fn temp_problem(&self) -> Option<&str> {
+        self.temp_problem.as_deref()
+    }
+
+    pub fn gyro_info(&self) -> &Vec<SampleInfo> {
+        &self.gyro_info
+    }
+
+    pub fn acc_info(&self) -> &Vec<SampleInfo> {
+        &self.acc_info
+    }
+
+    pub fn mag_info(&self) -> &Vec<SampleInfo> {
+        &self.mag_info
+    }
+
     pub fn clear_gyro_measurements(&mut self) {
         self.gyro_points.clear();
+        self.gyro_info.clear();
     }
 
     pub fn clear_accel_measurements(&mut self) {
         self.acc_points.clear();
+        self.acc_info.clear();
     }
 
     pub fn clear_mag_measurements(&mut self) {
         self.mag_points.clear();
+        self.mag_info.clear();
     }
 
     pub fn calibrate(&mut self) -> CalData {
-        // gyro
-        let gyro_offset = {
-            let sum_x: f64 = self.gyro_points.iter().map(|p| p.x).sum();
-            let sum_y: f64 = self.gyro_points.iter().map(|p| p.y).sum();
-            let sum_z: f64 = self.gyro_points.iter().map(|p| p.z).sum();
-            let count = self.gyro_points.len() as f64;
-
-            Vector3::new(sum_x / count, sum_y / count, sum_z / count)
-        };
+        // temperature, the other calibrations are done on the compensated points
+        let temp_cal = self.temp_comp.then(|| self.calibrate_temperature());
+        self.temp_problem = temp_cal.clone().and_then(Result::err);
+        let temp_cal = temp_cal.and_then(Result::ok);
+        let gyro_points = compensate(&self.gyro_points, &self.gyro_info, &temp_cal, |t| &t.gyro);
+        let acc_points = compensate(&self.acc_points, &self.acc_info, &temp_cal, |t| &t.acc);
+        let mag_points = compensate(&self.mag_points, &self.mag_info, &temp_cal, |t| &t.mag);
 
         // acc
-        let x_p: Vec<f64> = self
-            .acc_points
+        let gravity = self.gravity();
+        let poses: Vec<_> = poses::segment_static(&acc_points)
             .iter()
-            .map(|p| p.x)
-            .filter(|p| *p > G0_THR)
+            .map(|p| p.mean)
             .collect();
-        let x_m: Vec<f64> = self
-            .acc_points
-            .iter()
-            .map(|p| p.x)
-            .filter(|p| *p < -G0_THR)
-            .collect();
-        let y_p: Vec<f64> = self
-            .acc_points
-            .iter()
-            .map(|p| p.y)
-            .filter(|p| *p > G0_THR)
-            .collect();
-        let y_m: Vec<f64> = self
-            .acc_points
-            .iter()
-            .map(|p| p.y)
-            .filter(|p| *p < -G0_THR)
-            .collect();
-        let z_p: Vec<f64> = self
-            .acc_points
-            .iter()
-            .map(|p| p.z)
-            .filter(|p| *p > G0_THR)
-            .collect();
-        let z_m: Vec<f64> = self
-            .acc_points
+        let (acc_offset, acc_transf) = match self.acc_model {
+            AccModel::SixFace => Self::calibrate_acc_six_face(&acc_points, gravity),
+            AccModel::Ellipsoid => Self::fit_acc_ellipsoid(&poses, gravity).unwrap_or_else(|| {
+                println!(
+                    "Accel ellipsoid fit failed ({} poses), falling back to six faces",
+                    poses.len()
+                );
+                Self::calibrate_acc_six_face(&acc_points, gravity)
+            }),
+        };
+        // over the static poses, the motion in between is no calibration error
+        let residual_points = if poses.is_empty() {
+            &acc_points
+        } else {
+            &poses
+        };
+        let acc_residual = (residual_points
             .iter()
-            .map(|p| p.z)
-            .filter(|p| *p < -G0_THR)
-            .collect();
+            .map(|p| ((acc_transf * (p - acc_offset)).norm() - gravity).powi(2))
+            .sum::<f64>()
+            / residual_points.len() as f64)
+            .sqrt();
 
-        let acc_offset = {
-            let sx_p = x_p.iter().sum::<f64>() / x_p.len() as f64;
-            let sx_m = x_m.iter().sum::<f64>() / x_m.len() as f64;
-            let sy_p = y_p.iter().sum::<f64>() / y_p.len() as f64;
-            let sy_m = y_m.iter().sum::<f64>() / y_m.len() as f64;
-            let sz_p = z_p.iter().sum::<f64>() / z_p.len() as f64;
-            let sz_m = z_m.iter().sum::<f64>() / z_m.len() as f64;
-
-            Vector3::new(
-                (sx_p + sx_m) * 0.5,
-                (sy_p + sy_m) * 0.5,
-                (sz_p + sz_m) * 0.5,
-            )
+        // gyro
+        let mean_offset = || {
+            let sum_x: f64 = gyro_points.iter().map(|p| p.x).sum();
+            let sum_y: f64 = gyro_points.iter().map(|p| p.y).sum();
+            let sum_z: f64 = gyro_points.iter().map(|p| p.z).sum();
+            let count = gyro_points.len() as f64;
+
+            Vector3::new(sum_x / count, sum_y / count, sum_z / count)
         };
-        let acc_scale = {
-            let range_x: f64 = (x_p.iter().sum::<f64>() / x_p.len() as f64)
-                - (x_m.iter().sum::<f64>() / x_m.len() as f64);
-            let range_y: f64 = (y_p.iter().sum::<f64>() / y_p.len() as f64)
-                - (y_m.iter().sum::<f64>() / y_m.len() as f64);
-            let range_z: f64 = (z_p.iter().sum::<f64>() / z_p.len() as f64)
-                - (z_m.iter().sum::<f64>() / z_m.len() as f64);
-
-            let scale_x = 2.0 * G0 / range_x;
-            let scale_y = 2.0 * G0 / range_y;
-            let scale_z = 2.0 * G0 / range_z;
-
-            Vector3::new(scale_x, scale_y, scale_z)
+        let scale_cal = match self.gyro_model {
+            GyroModel::Offset => None,
+            GyroModel::KnownTurns => {
+                gyro_cal::calibrate_known_turns(&gyro_points, &self.gyro_info, self.gyro_turns)
+            }
+            GyroModel::Turntable => {
+                gyro_cal::calibrate_turntable(&gyro_points, self.turntable_rate)
+            }
+            GyroModel::GravityReference => gyro_cal::calibrate_gravity_reference(
+                &gyro_points,
+                &self.gyro_info,
+                &acc_points,
+                &self.acc_info,
+                |a| acc_transf * (a - acc_offset),
+            ),
         };
+        if self.gyro_model != GyroModel::Offset && scale_cal.is_none() {
+            println!("Gyro scale calibration failed, rotations about all three axes with still periods in between are required");
+        }
+        let (gyro_offset, gyro_transf) =
+            scale_cal.unwrap_or_else(|| (mean_offset(), Matrix3::identity()));
+        let gyro_acc_rotation = match (self.gyro_model, scale_cal) {
+            (GyroModel::GravityReference, Some(_)) => {
+                if gyro_transf.determinant() < 0.0 {
+                    println!(
+                        "Gyro correction mirrors the axes, check the gyro and accel axis maps"
+                    );
+                }
+                *gyro_cal::split_frame_rotation(&gyro_transf).0.matrix()
+            }
+            _ => Matrix3::identity(),
+        };
+        let g_sensitivity_cal = if self.gyro_g_sensitivity {
+            let cal = gyro_cal::calibrate_g_sensitivity(
+                &gyro_points,
+                &self.gyro_info,
+                &acc_points,
+                &self.acc_info,
+                |a| acc_transf * (a - acc_offset),
+            );
+            if cal.is_none() {
+                println!("Gyro g-sensitivity calibration failed, still periods in at least six orientations with gyro and accel samples are required");
+            }
+            cal
+        } else {
+            None
+        };
+        let gyro_offset = g_sensitivity_cal.map_or(gyro_offset, |(bias, _)| bias);
 
         // mag
         //
         // refs:
         // https://teslabs.com/articles/magnetometer-calibration/
-        let (a1, b) = if !self.mag_points.is_empty() {
-            let (m, n, d) = Self::fit_mag_ellipsoid(&self.mag_points);
-            Self::cac_mag_params_from_fit(F0, m, n, d)
+        let expected_field = self.expected_field();
+        let mag_field = match self.mag_scale {
+            MagScale::Field => expected_field.map_or(F0, |f| f.intensity()),
+            MagScale::UnitSphere => 1.0,
+        };
+        let ellipsoid = match self.mag_model {
+            MagModel::Sphere => None,
+            MagModel::Ellipsoid => Self::fit_mag_ellipsoid(&mag_points),
+            MagModel::Auto => (mag_spread(&mag_points) > MIN_MAG_SPREAD)
+                .then(|| Self::fit_mag_ellipsoid(&mag_points))
+                .flatten(),
+        };
+        let ellipsoid =
+            ellipsoid.and_then(|(m, n, d)| Self::cac_mag_params_from_fit(mag_field, m, n, d));
+        let (mag_model, a1, b) = if let Some((a1, b)) = ellipsoid {
+            (MagModel::Ellipsoid, a1, b)
+        } else if let Some((center, radius)) = Self::fit_mag_sphere(&mag_points) {
+            if self.mag_model == MagModel::Ellipsoid {
+                println!("mag ellipsoid fit failed, using the sphere fit");
+            }
+            (
+                MagModel::Sphere,
+                Matrix3::identity() * (mag_field / radius),
+                center,
+            )
         } else {
-            (Matrix3::identity(), Vector3::zeros())
+            if !mag_points.is_empty() {
+                println!("mag samples cover too little of the sphere, mag left uncalibrated");
+            }
+            (MagModel::Sphere, Matrix3::identity(), Vector3::zeros())
         };
 
         let mut mag_quality = Quality::default();
         mag_quality.reset();
-        for p in &self.mag_points {
+        for p in &mag_points {
             mag_quality.update(a1 * (*p - b));
         }
 
+        // inclination, angle between the calibrated mag and the horizontal plane from the
+        // accel while standing still
+        let mag_inclination = {
+            let acc_index = latest_samples(&self.mag_info, &self.acc_info);
+            let inclinations: Vec<_> = mag_points
+                .iter()
+                .zip(&acc_index)
+                .filter_map(|(m, i)| {
+                    let m = a1 * (m - b);
+                    let a = acc_transf * (acc_points[*i] - acc_offset);
+                    ((a.norm() - gravity).abs() < STILL_ACC_TOLERANCE * gravity)
+                        .then(|| (-a.normalize()).dot(&m.normalize()).asin())
+                })
+                .collect();
+            (!inclinations.is_empty())
+                .then(|| inclinations.iter().sum::<f64>() / inclinations.len() as f64)
+        };
+
+        // Earth rate, from the orientation while the gyro samples were taken. Only if they
+        // were all taken in one static pose, the mean orientation of several poses is none.
+        let earth_rate = if !self.earth_rate {
+            None
+        } else if self.gyro_model != GyroModel::Offset {
+            println!("Earth rate removal needs the offset gyro model, the scale calibrations rotate the gyro");
+            None
+        } else if g_sensitivity_cal.is_some() {
+            println!("Earth rate removal can't be combined with the g-sensitivity calibration, its bias absorbs the Earth rate");
+            None
+        } else {
+            let span = gyro_cal::sample_times(&self.gyro_info)
+                .and_then(|t| Some((*t.first()?, *t.last()?)));
+            let acc = mean_during(&acc_points, &self.acc_info, span)
+                .map(|a| acc_transf * (a - acc_offset));
+            let mag = mean_during(&mag_points, &self.mag_info, span).map(|m| a1 * (m - b));
+            if let (Some(acc), Some(mag)) = (acc, mag) {
+                let acc_samples = samples_during(&acc_points, &self.acc_info, span);
+                if poses::segment_static(&acc_samples).len() == 1 {
+                    earth::earth_rate_body(&acc, &mag, self.latitude, self.magnetic_declination())
+                } else {
+                    println!(
+                        "Earth rate removal needs the gyro samples taken in a single static pose"
+                    );
+                    None
+                }
+            } else {
+                println!("Earth rate removal needs accel and mag samples of the static pose");
+                None
+            }
+        };
+        // the offset model leaves the gyro unscaled, the rate is removed as it is
+        let gyro_offset = earth_rate.map_or(gyro_offset, |rate| gyro_offset - rate);
+
         let cal_data = CalData {
             gyro_offset,
+            gyro_transf,
+            gyro_acc_rotation,
             acc_offset,
-            acc_scale,
+            acc_scale: acc_transf.diagonal(),
+            acc_transf,
+            acc_residual,
+            gravity,
             soft_iron_transf: a1,
             hard_iron_bias: b,
             mag_quality,
+            gyro_axis_map: self.axis_maps.gyro.matrix(),
+            acc_axis_map: self.axis_maps.acc.matrix(),
+            mag_axis_map: self.axis_maps.mag.matrix(),
+            temp_cal,
+            gyro_g_sensitivity: g_sensitivity_cal.map(|(_, s)| s),
+            earth_rate,
+            mag_model,
+            mag_scale: self.mag_scale,
+            mag_field,
+            mag_declination: expected_field.map(|f| f.declination()),
+            mag_inclination_expected: expected_field.map(|f| f.inclination()),
+            mag_inclination,
         };
         self.cal_data = Some(cal_data.clone());
 
         cal_data
     }
 
+    /// Still period of each sample of the sensor, found in the accel data, None while
+    /// moving. Without accel data the whole recording is taken as one still period.
+    fn still_periods(&self, sensor: SensorKind) -> Vec<Option<usize>> {
+        if self.acc_points.is_empty() {
+            let len = match sensor {
+                SensorKind::Gyro => self.gyro_points.len(),
+                SensorKind::Acc => 0,
+                SensorKind::Mag => self.mag_points.len(),
+            };
+            return vec![Some(0); len];
+        }
+
+        let mut acc_pose = vec![None; self.acc_points.len()];
+        for (i, range) in poses::static_ranges(&self.acc_points)
+            .into_iter()
+            .enumerate()
+        {
+            acc_pose[range].fill(Some(i));
+        }
+
+        match sensor {
+            SensorKind::Acc => acc_pose,
+            SensorKind::Gyro | SensorKind::Mag => {
+                let info = match sensor {
+                    SensorKind::Gyro => &self.gyro_info,
+                    _ => &self.mag_info,
+                };
+                latest_samples(info, &self.acc_info)
+                    .into_iter()
+                    .map(|i| acc_pose.get(i).copied().flatten())
+                    .collect()
+            }
+        }
+    }
+
+    /// Sample ranges of the sensor within the still periods of the accel data, the whole
+    /// recording if there is no accel data.
+    pub fn still_ranges(&self, sensor: SensorKind) -> Vec<Range<usize>> {
+        let periods = self.still_periods(sensor);
+        let mut ranges: Vec<Range<usize>> = vec![];
+        for (i, period) in periods.iter().enumerate() {
+            if period.is_none() {
+                continue;
+            }
+            match ranges.last_mut() {
+                Some(r) if r.end == i && periods[r.start] == *period => r.end = i + 1,
+                _ => ranges.push(i..i + 1),
+            }
+        }
+        ranges
+    }
+
+    /// Samples of the still periods, grouped by period, for the temperature drift of the
+    /// given sensor.
+    pub fn temperature_poses(&self, sensor: SensorKind) -> Vec<temp_comp::PoseSamples> {
+        let (points, info) = match sensor {
+            SensorKind::Gyro => (&self.gyro_points, &self.gyro_info),
+            SensorKind::Acc => (&self.acc_points, &self.acc_info),
+            SensorKind::Mag => (&self.mag_points, &self.mag_info),
+        };
+        let temperatures: Vec<_> = info.iter().map(|i| i.temperature).collect();
+        temp_comp::group_by_pose(points, &temperatures, &self.still_periods(sensor))
+    }
+
+    /// Drift curves of the sensors, or why none could be fitted.
+    fn calibrate_temperature(&self) -> Result<TempCal, String> {
+        let t_ref = temp_comp::mean_temperature(&[&self.gyro_info, &self.acc_info, &self.mag_info])
+            .ok_or("the samples carry no temperature")?;
+        if !self.acc_points.is_empty() && poses::static_ranges(&self.acc_points).is_empty() {
+            return Err("no still periods found in the accel data".to_string());
+        }
+        let fit = |sensor: SensorKind, scale: bool| {
+            temp_comp::fit_curve(
+                &self.temperature_poses(sensor),
+                t_ref,
+                self.temp_degree,
+                scale,
+            )
+        };
+
+        let temp_cal = TempCal {
+            t_ref,
+            degree: self.temp_degree,
+            gyro: fit(SensorKind::Gyro, false),
+            acc: fit(SensorKind::Acc, self.temp_scale),
+            mag: fit(SensorKind::Mag, self.temp_scale),
+        };
+        if temp_cal.gyro.is_none() && temp_cal.acc.is_none() && temp_cal.mag.is_none() {
+            return Err(format!(
+                "no still period spans {} °C or more",
+                temp_comp::MIN_SPAN
+            ));
+        }
+        Ok(temp_cal)
+    }
+
+    /// Per axis offset and scale from the samples with the axis pointing up or down. Axes
+    /// with a missing face are left uncalibrated.
+    fn calibrate_acc_six_face(points: &[Vector3<f64>], g: f64) -> (Vector3<f64>, Matrix3<f64>) {
+        let mut offset = Vector3::zeros();
+        let mut scale = Vector3::repeat(1.0);
+
+        for axis in 0..3 {
+            let mean = |values: Vec<f64>| {
+                (!values.is_empty()).then(|| values.iter().sum::<f64>() / values.len() as f64)
+            };
+            let up = mean(
+                points
+                    .iter()
+                    .map(|p| p[axis])
+                    .filter(|v| *v > G0_THR)
+                    .collect(),
+            );
+            let down = mean(
+                points
+                    .iter()
+                    .map(|p| p[axis])
+                    .filter(|v| *v < -G0_THR)
+                    .collect(),
+            );
+
+            match (up, down) {
+                (Some(up), Some(down)) => {
+                    offset[axis] = (up + down) * 0.5;
+                    scale[axis] = 2.0 * g / (up - down);
+                }
+                _ => println!("Accel axis {} is missing a face, left uncalibrated", axis),
+            }
+        }
+
+        (offset, Matrix3::from_diagonal(&scale))
+    }
+
+    /// Fits `|T * (a - b)| = g` to static samples in arbitrary orientations, T being upper
+    /// triangular (scale and misalignment, the rotation is not observable). Levenberg-
+    /// Marquardt, starting from the identity.
+    ///
+    /// refs:
+    /// D. Tedaldi, A. Pretto, E. Menegatti, "A robust and easy to implement method for IMU
+    /// calibration without external equipments", ICRA 2014
+    pub fn fit_acc_ellipsoid(
+        points: &[Vector3<f64>],
+        g: f64,
+    ) -> Option<(Vector3<f64>, Matrix3<f64>)> {
+        const UPPER: [(usize, usize); 6] = [(0, 0), (0, 1), (0, 2), (1, 1), (1, 2), (2, 2)];
+
+        if points.len() < 9 {
+            return None;
+        }
+
+        let params_to_model = |x: &nalgebra::SVector<f64, 9>| {
+            let mut t = Matrix3::zeros();
+            for (i, (r, c)) in UPPER.iter().enumerate() {
+                t[(*r, *c)] = x[i];
+            }
+            (t, Vector3::new(x[6], x[7], x[8]))
+        };
+        let cost = |x: &nalgebra::SVector<f64, 9>| {
+            let (t, b) = params_to_model(x);
+            points
+                .iter()
+                .map(|p| ((t * (p - b)).norm() - g).powi(2))
+                .sum::<f64>()
+        };
+
+        let mut x = nalgebra::SVector::<f64, 9>::from_column_slice(&[
+            1.0, 0.0, 0.0, 1.0, 0.0, 1.0, 0.0, 0.0, 0.0,
+        ]);
+        let mut lambda = 1e-3;
+        let mut current_cost = cost(&x);
+
+        for _ in 0..100 {
+            let (t, b) = params_to_model(&x);
+            let mut jtj = nalgebra::SMatrix::<f64, 9, 9>::zeros();
+            let mut jtr = nalgebra::SVector::<f64, 9>::zeros();
+
+            for p in points {
+                let d = p - b;
+                let u = t * d;
+                let norm = u.norm();
+                let n = u / norm;
+
+                let mut j = nalgebra::SVector::<f64, 9>::zeros();
+                for (i, (r, c)) in UPPER.iter().enumerate() {
+                    j[i] = n[*r] * d[*c];
+                }
+                let j_b = -(t.transpose() * n);
+                j.fixed_rows_mut::<3>(6).copy_from(&j_b);
+
+                jtj += j * j.transpose();
+                jtr += j * (norm - g);
+            }
+
+            let mut damped = jtj;
+            for i in 0..9 {
+                damped[(i, i)] += lambda * jtj[(i, i)].max(1e-12);
+            }
+            let step = damped.cholesky()?.solve(&-jtr);
+            let candidate = x + step;
+            let candidate_cost = cost(&candidate);
+
+            if candidate_cost < current_cost {
+                x = candidate;
+                current_cost = candidate_cost;
+                lambda *= 0.1;
+                if step.norm() < 1e-12 {
+                    break;
+                }
+            } else {
+                lambda *= 10.0;
+            }
+        }
+
+        let (t, b) = params_to_model(&x);
+        (t.determinant() > 0.0 && current_cost.is_finite()).then_some((b, t))
+    }
+
+    /// Soft iron transform and hard iron offset of the fitted quadric, None if it encloses
+    /// no points (nᵀM⁻¹n - d ≤ 0).
     pub fn cac_mag_params_from_fit(
         f: f64, // magnitude of the magnetic field, this can be 1.0 for navigation as the magnitude does not matter
         m: nalgebra::Matrix3<f64>,
         n: Vector3<f64>,
         d: f64,
-    ) -> (Matrix3<f64>, Vector3<f64>) {
-        let m_1 = m.try_inverse().unwrap();
+    ) -> Option<(Matrix3<f64>, Vector3<f64>)> {
+        let m_1 = m.try_inverse()?;
         let b = -(m_1 * n);
 
         let m_msqrt = mat3_m_sqrt(m, 10);
 
-        let x1 = (n.transpose() * (m_1 * n)).add_scalar(-d);
-        let x1_sqrt = x1.map(|x| x.sqrt()); // element-wise
+        let x1 = n.dot(&(m_1 * n)) - d;
+
+        (x1 > 0.0).then(|| (m_msqrt * (f / x1.sqrt()), b))
+    }
+
+    /// Hard iron offset and radius of the sphere best fitting the points, linear least
+    /// squares of |p|² = 2 p·c + r² - |c|². The points are centered and scaled first, None
+    /// if they don't determine the sphere, e.g. all on one circle.
+    pub fn fit_mag_sphere(mag_points: &[Vector3<f64>]) -> Option<(Vector3<f64>, f64)> {
+        if mag_points.len() < 4 {
+            return None;
+        }
+        let mean = mag_points.iter().sum::<Vector3<f64>>() / mag_points.len() as f64;
+        let scale = (mag_points
+            .iter()
+            .map(|p| (p - mean).norm_squared())
+            .sum::<f64>()
+            / mag_points.len() as f64)
+            .sqrt();
+        if scale <= 0.0 {
+            return None;
+        }
+        let q: Vec<_> = mag_points.iter().map(|p| (p - mean) / scale).collect();
 
-        let a_1 = m_msqrt * (f / x1_sqrt[0]);
+        let a = DMatrix::from_fn(q.len(), 4, |i, j| if j < 3 { 2.0 * q[i][j] } else { 1.0 });
+        let y = DVector::from_fn(q.len(), |i, _| q[i].norm_squared());
+        let svd = a.svd(true, true);
+        if svd.singular_values.min() < MIN_SPHERE_CONDITION * svd.singular_values.max() {
+            return None;
+        }
+        let x = svd.solve(&y, 1e-12).ok()?;
 
-        (a_1, b)
+        let center = Vector3::new(x[0], x[1], x[2]);
+        let r2 = x[3] + center.norm_squared();
+        (r2 > 0.0).then(|| (mean + center * scale, r2.sqrt() * scale))
     }
 
     pub fn fit_mag_ellipsoid(
         mag_points: &[Vector3<f64>],
-    ) -> (nalgebra::Matrix3<f64>, Vector3<f64>, f64) {
+    ) -> Option<(nalgebra::Matrix3<f64>, Vector3<f64>, f64)> {
         // refs:
         // https://ieeexplore.ieee.org/abstract/document/1290055/
         // https://github.com/nliaudat/magnetometer_calibration/blob/main/calibrate.py
@@ -351,18 +1113,16 @@ impl Cal {
         ];
 
         let c_inv = c.try_inverse().unwrap();
-        let s_22_inv = s_22.try_inverse().unwrap();
+        let s_22_inv = s_22.try_inverse()?;
 
         let e = c_inv * (s_11 - s_12 * (s_22_inv * s_21));
-        let s = nalgebra::Schur::new(e);
-        let (q, _) = s.unpack();
-
-        let e_w = s.eigenvalues().unwrap();
-        let e_v = q.column(0);
 
-        // println!("E {}", e_v);
-
-        let mut v_1 = (e_v.column(e_w.argmax().0)).clone_owned();
+        // eigenvector of the largest eigenvalue, the null space of E - w I
+        let e_w = e.eigenvalues()?;
+        let w = e_w.max();
+        let svd = (e - nalgebra::Matrix6::identity() * w).svd(false, true);
+        let v_t = svd.v_t?;
+        let mut v_1 = v_t.row(svd.singular_values.imin()).transpose();
 
         if v_1[0] < 0.0 {
             v_1.neg_mut();
@@ -380,11 +1140,10 @@ impl Cal {
 
         let d = v_2[3];
 
-        // println!("{}", E_v);
-        // println!("{}", E_w);
-        // println!("{}", v_2);
+        // the quadric must be an ellipsoid
+        m.cholesky()?;
 
-        (m, n, d)
+        Some((m, n, d))
     }
 }
 
@@ -404,6 +1163,125 @@ pub fn mat3_m_sqrt(a: nalgebra::Matrix3<f64>, iter_count: usize) -> nalgebra::Ma
 mod test {
     use super::*;
 
+    #[test]
+    fn earth_rate_single_pose() {
+        let mut cal = Cal::new();
+        cal.earth_rate = true;
+        cal.latitude = 48f64.to_radians();
+        let add_pose = |cal: &mut Cal, start: usize, acc: Vector3<f64>| {
+            for i in start..start + 100 {
+                let info = SampleInfo {
+                    host_time: Some(i as f64 * 0.01),
+                    ..Default::default()
+                };
+                cal.add_gyro_measurement(Vector3::new(1e-5, 0.0, -5e-5), info);
+                cal.add_acc_measurement(acc, info);
+                cal.add_mag_measurement(Vector3::new(20.0, 0.0, 40.0), info);
+            }
+        };
+
+        add_pose(&mut cal, 0, Vector3::new(0.0, 0.0, G0));
+        assert!(cal.calibrate().earth_rate.is_some());
+
+        // the mean of two poses is no orientation
+        add_pose(&mut cal, 100, Vector3::new(G0, 0.0, 0.0));
+        assert!(cal.calibrate().earth_rate.is_none());
+    }
+
+    #[test]
+    fn gyro_only_temperature_sweep() {
+        let mut cal = Cal::new();
+        cal.temp_comp = true;
+        cal.temp_degree = 1;
+        for i in 0..500 {
+            let temperature = 20.0 + i as f64 * 0.02;
+            let info = SampleInfo {
+                temperature: Some(temperature),
+                ..Default::default()
+            };
+            cal.add_gyro_measurement(Vector3::new(1e-3, -2e-3, 5e-4) * (temperature - 25.0), info);
+        }
+
+        let temp_cal = cal.calibrate().temp_cal.unwrap();
+        let curve = temp_cal.gyro.unwrap();
+        let slope = Vector3::new(1e-3, -2e-3, 5e-4);
+        assert!(
+            (curve.bias_drift(30.0, temp_cal.t_ref) - slope * (30.0 - temp_cal.t_ref)).norm()
+                < 1e-9
+        );
+        assert!(cal.temp_problem().is_none());
+
+        // without temperature there is nothing to fit
+        let mut cal = Cal::new();
+        cal.temp_comp = true;
+        cal.add_gyro_measurement(Vector3::zeros(), SampleInfo::default());
+        assert!(cal.calibrate().temp_cal.is_none());
+        assert!(cal.temp_problem().is_some());
+    }
+
+    #[test]
+    fn acc_residual_of_poses() {
+        let mut cal = Cal::new();
+        for axis in Axis::ALL {
+            for _ in 0..50 {
+                cal.add_acc_measurement(axis.unit_vector() * G0, SampleInfo::default());
+            }
+            // turning to the next face
+            for j in 0..5 {
+                cal.add_acc_measurement(Vector3::new(3.0, j as f64, 4.0), SampleInfo::default());
+            }
+        }
+
+        let cal_data = cal.calibrate();
+        assert!(cal_data.acc_residual < 1e-9);
+    }
+
+    #[test]
+    fn mag_unit_sphere() {
+        let mut cal = Cal::new();
+        cal.mag_scale = MagScale::UnitSphere;
+        for p in MAG_TEST_DATA {
+            cal.add_mag_measurement(Vector3::new(p[0], p[1], p[2]), SampleInfo::default());
+        }
+
+        let cal_data = cal.calibrate();
+        let norms: Vec<_> = cal
+            .mag_measurements_with_cal()
+            .iter()
+            .map(|p| p.norm())
+            .collect();
+        let mean = norms.iter().sum::<f64>() / norms.len() as f64;
+        assert!((mean - 1.0).abs() < 0.01, "{mean}");
+        assert!(norms.iter().all(|n| (n - 1.0).abs() < 0.05));
+        assert!(serde_json::to_string(&cal_data)
+            .unwrap()
+            .contains(r#""mag_scale":"unit_sphere""#));
+    }
+
+    #[test]
+    fn acc_ellipsoid() {
+        let t = Matrix3::new(1.02, 0.01, -0.02, 0.0, 0.97, 0.015, 0.0, 0.0, 1.01);
+        let b = Vector3::new(0.2, -0.15, 0.3);
+        let t_inv = t.try_inverse().unwrap();
+
+        // static poses all around, raw = T^-1 * g + b
+        let points: Vec<_> = (0..30)
+            .map(|i| {
+                let (theta, phi) = (i as f64 * 0.7, i as f64 * 2.3);
+                let g = Vector3::new(
+                    theta.sin() * phi.cos(),
+                    theta.sin() * phi.sin(),
+                    theta.cos(),
+                );
+                t_inv * g * G0 + b
+            })
+            .collect();
+
+        let (offset, transf) = Cal::fit_acc_ellipsoid(&points, G0).unwrap();
+        assert!((offset - b).norm() < 1e-6);
+        assert!((transf - t).norm() < 1e-6);
+    }
+
     #[test]
     fn mat_msqrt() {
         let a = nalgebra::matrix![1.0,3.0; 1.0,4.0];
@@ -439,7 +1317,7 @@ mod test {
         // d =
         // 220982.468485425
 
-        let (m, n, d) = Cal::fit_mag_ellipsoid(&mag_points);
+        let (m, n, d) = Cal::fit_mag_ellipsoid(&mag_points).unwrap();
         println!("M {}, n {}, d {}", m, n, d);
 
         // calculate with
@@ -454,7 +1332,7 @@ mod test {
         // [-89.87465738];
         // [569.66392911]]
 
-        let (a_1, b) = Cal::cac_mag_params_from_fit(1000.0, m, n, d);
+        let (a_1, b) = Cal::cac_mag_params_from_fit(1000.0, m, n, d).unwrap();
         println!("A_1 {}, b {}", a_1, b);
 
         // calculate with
@@ -467,6 +1345,100 @@ mod test {
         println!("first element with cal: {}", a_1 * (mag_point - b))
     }
 
+    #[test]
+    fn ellipsoid_eigenvector() {
+        // the reference values of the fit test above
+        let mag_points: Vec<_> = MAG_TEST_DATA
+            .iter()
+            .map(|p| nalgebra::vector![p[0], p[1], p[2]])
+            .collect();
+        let (m, n, d) = Cal::fit_mag_ellipsoid(&mag_points).unwrap();
+        let (a_1, b) = Cal::cac_mag_params_from_fit(1000.0, m, n, d).unwrap();
+        assert!((b - Vector3::new(41.16886643, -89.87465738, 569.66392911)).norm() < 1e-3);
+        assert!(
+            (a_1 * (mag_points[0] - b) - Vector3::new(-32.66246512, 1004.79174348, 22.6600199))
+                .norm()
+                < 1e-3
+        );
+
+        // an exact ellipsoid, A_1 is the symmetric soft iron correction
+        let soft_iron = nalgebra::matrix![
+            1.2, 0.1, -0.05;
+            0.1, 0.9, 0.02;
+            -0.05, 0.02, 1.1
+        ];
+        let hard_iron = Vector3::new(12.0, -30.0, 45.0);
+        let points: Vec<_> = (0..200)
+            .map(|i| {
+                let (theta, phi) = (i as f64 * 0.7, i as f64 * 2.3);
+                let u = Vector3::new(
+                    theta.sin() * phi.cos(),
+                    theta.sin() * phi.sin(),
+                    theta.cos(),
+                );
+                soft_iron.try_inverse().unwrap() * u * 50.0 + hard_iron
+            })
+            .collect();
+        let (m, n, d) = Cal::fit_mag_ellipsoid(&points).unwrap();
+        let (a_1, b) = Cal::cac_mag_params_from_fit(50.0, m, n, d).unwrap();
+        assert!((b - hard_iron).norm() < 1e-6);
+        assert!((a_1 - soft_iron).norm() < 1e-6);
+    }
+
+    #[test]
+    fn degenerate_quadric() {
+        // |p|² = -1 holds for no point
+        let m = Matrix3::identity();
+        assert!(Cal::cac_mag_params_from_fit(1.0, m, Vector3::zeros(), 1.0).is_none());
+        assert!(Cal::cac_mag_params_from_fit(1.0, m * 0.0, Vector3::zeros(), -1.0).is_none());
+
+        let (a_1, b) = Cal::cac_mag_params_from_fit(2.0, m, Vector3::zeros(), -4.0).unwrap();
+        assert!((a_1 - Matrix3::identity()).norm() < 1e-9);
+        assert!(b.norm() < 1e-9);
+    }
+
+    #[test]
+    fn sphere_fit() {
+        // rotation about z only, the ellipsoid is undetermined along z
+        let center = Vector3::new(12.0, -30.0, 45.0);
+        let points: Vec<_> = (0..100)
+            .map(|i| {
+                let a = i as f64 * 0.0628;
+                center + Vector3::new(20.0 * a.cos(), 20.0 * a.sin(), 40.0)
+            })
+            .collect();
+        assert!(mag_spread(&points) < MIN_MAG_SPREAD);
+        assert!(Cal::fit_mag_sphere(&points).is_none());
+
+        // rotated about z while tilted by up to ±40° about x
+        let cap: Vec<_> = (0..200)
+            .map(|i| {
+                let (a, tilt) = (i as f64 * 0.0628, (i as f64 * 0.3).sin() * 0.7);
+                center + 48.0 * Vector3::new(a.cos(), a.sin() * tilt.cos(), a.sin() * tilt.sin())
+            })
+            .collect();
+        let (c, r) = Cal::fit_mag_sphere(&cap).unwrap();
+        assert!((c - center).norm() < 1e-9);
+        assert!((r - 48.0).abs() < 1e-9);
+
+        // offset and radius of a full sphere
+        let sphere: Vec<_> = (0..200)
+            .map(|i| {
+                let (theta, phi) = (i as f64 * 0.7, i as f64 * 2.3);
+                center
+                    + 48.0
+                        * Vector3::new(
+                            theta.sin() * phi.cos(),
+                            theta.sin() * phi.sin(),
+                            theta.cos(),
+                        )
+            })
+            .collect();
+        let (c, r) = Cal::fit_mag_sphere(&sphere).unwrap();
+        assert!((c - center).norm() < 1e-9);
+        assert!((r - 48.0).abs() < 1e-9);
+    }
+
     const MAG_TEST_DATA: [[f64; 3]; 243] = [
         [33.1, 98.3, 571.2],
         [33.1, 98.3, 571.2],
@@ -1,17 +1,56 @@
+use crate::units::InputUnits;
 use eframe::egui;
 use nalgebra::Vector3;
+use std::time::{SystemTime, UNIX_EPOCH};
 
-#[derive(Debug, Clone, Copy)]
+/// Timing and temperature that come along with a measurement. Every field is optional as
+/// not all providers are able to fill them in.
+#[derive(Debug, Clone, Copy, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct SampleInfo {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub device_time: Option<f64>, // s, device clock
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub host_time: Option<f64>, // s since unix epoch, time of reception
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub seq: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f64>, // °C
+}
+
+impl SampleInfo {
+    pub fn received_now() -> Self {
+        Self {
+            host_time: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .ok()
+                .map(|d| d.as_secs_f64()),
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
 pub struct ImuData {
     pub lin_acc: Vector3<f64>,
     pub ang_vel: Vector3<f64>,
+    pub info: SampleInfo,
+    pub device: String, // empty if the provider does not tell devices apart
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone)]
 pub struct MagData {
     pub field: Vector3<f64>,
+    pub info: SampleInfo,
+    pub device: String,
 }
 
 pub trait DataProviderUi {
     fn show(&mut self, ui: &mut egui::Ui);
+
+    /// Units of the sent data, `None` if they depend on the sender and have to be chosen by
+    /// the user.
+    fn input_units(&self) -> Option<InputUnits>;
+
+    /// Value the sample sequence counter wraps at, `None` if it does not wrap.
+    fn seq_modulus(&self) -> Option<u64>;
 }
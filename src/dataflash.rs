@@ -0,0 +1,292 @@
+// ArduPilot DataFlash (.bin) log reader
+//
+// refs:
+// https://ardupilot.org/dev/docs/code-overview-adding-a-new-log-message.html
+// https://github.com/ArduPilot/pymavlink/blob/master/DFReader.py
+
+use crate::log_import::{stream_mut, LogSample, LogStream, SensorKind};
+use crate::units::{InputUnits, MagUnit};
+use nalgebra::Vector3;
+use std::collections::HashMap;
+use std::io::{Error, ErrorKind};
+
+const HEAD: [u8; 2] = [0xA3, 0x95];
+const FMT_TYPE: u8 = 0x80;
+const FMT_LEN: usize = 89;
+
+/// Units used by ArduPilot: m/s², rad/s and milligauss
+pub const UNITS: InputUnits = InputUnits::with_mag(MagUnit::MilliGauss);
+
+#[derive(Debug)]
+struct Format {
+    name: String,
+    length: usize,
+    format: Vec<u8>,
+    labels: Vec<String>,
+}
+
+impl Format {
+    fn label_index(&self, label: &str) -> Option<usize> {
+        self.labels.iter().position(|l| l == label)
+    }
+}
+
+fn field_size(c: u8) -> usize {
+    match c {
+        b'b' | b'B' | b'M' => 1,
+        b'h' | b'H' | b'c' | b'C' => 2,
+        b'i' | b'I' | b'f' | b'e' | b'E' | b'L' | b'n' => 4,
+        b'd' | b'q' | b'Q' => 8,
+        b'N' => 16,
+        b'Z' | b'a' => 64,
+        _ => 0,
+    }
+}
+
+/// Decodes all numeric fields of a message. Strings and arrays decode to NaN.
+fn decode(format: &Format, payload: &[u8]) -> Vec<f64> {
+    let mut values = Vec::with_capacity(format.format.len());
+    let mut pos = 0;
+
+    for &c in &format.format {
+        let size = field_size(c);
+        let Some(b) = payload.get(pos..pos + size) else {
+            break;
+        };
+        pos += size;
+
+        values.push(match c {
+            b'b' => b[0] as i8 as f64,
+            b'B' | b'M' => b[0] as f64,
+            b'h' => i16::from_le_bytes([b[0], b[1]]) as f64,
+            b'H' => u16::from_le_bytes([b[0], b[1]]) as f64,
+            b'c' => i16::from_le_bytes([b[0], b[1]]) as f64 * 0.01,
+            b'C' => u16::from_le_bytes([b[0], b[1]]) as f64 * 0.01,
+            b'i' => i32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f64,
+            b'I' => u32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f64,
+            b'e' => i32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f64 * 0.01,
+            b'E' => u32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f64 * 0.01,
+            b'L' => i32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f64 * 1e-7,
+            b'f' => f32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f64,
+            b'd' => f64::from_le_bytes(b.try_into().unwrap()),
+            b'q' => i64::from_le_bytes(b.try_into().unwrap()) as f64,
+            b'Q' => u64::from_le_bytes(b.try_into().unwrap()) as f64,
+            _ => f64::NAN,
+        });
+    }
+
+    values
+}
+
+fn c_string(bytes: &[u8]) -> String {
+    let end = bytes.iter().position(|b| *b == 0).unwrap_or(bytes.len());
+    String::from_utf8_lossy(&bytes[..end]).to_string()
+}
+
+/// Instance number of a message. Newer logs carry an `I` field, older ones
+/// use separate message names (`IMU`, `IMU2`, `IMU3`).
+fn instance(format: &Format, values: &[f64], base_name: &str) -> Option<usize> {
+    if let Some(i) = format.label_index("I") {
+        return values.get(i).map(|i| *i as usize);
+    }
+    match format.name.strip_prefix(base_name)? {
+        "" => Some(0),
+        n => n.parse::<usize>().ok()?.checked_sub(1),
+    }
+}
+
+fn vector(format: &Format, values: &[f64], labels: [&str; 3]) -> Option<Vector3<f64>> {
+    Some(Vector3::new(
+        *values.get(format.label_index(labels[0])?)?,
+        *values.get(format.label_index(labels[1])?)?,
+        *values.get(format.label_index(labels[2])?)?,
+    ))
+}
+
+pub fn parse(data: &[u8]) -> std::io::Result<Vec<LogStream>> {
+    if data.len() < 3 || data[..2] != HEAD {
+        return Err(Error::new(ErrorKind::InvalidData, "Not a DataFlash log"));
+    }
+
+    let mut formats: HashMap<u8, Format> = HashMap::new();
+    let mut streams = vec![];
+
+    let mut pos = 0;
+    while pos + 3 <= data.len() {
+        if data[pos..pos + 2] != HEAD {
+            pos += 1; // resync after corrupted data
+            continue;
+        }
+
+        let msg_type = data[pos + 2];
+        let length = if msg_type == FMT_TYPE {
+            FMT_LEN
+        } else {
+            match formats.get(&msg_type) {
+                Some(format) => format.length,
+                None => {
+                    pos += 1;
+                    continue;
+                }
+            }
+        };
+
+        let Some(payload) = data.get(pos + 3..pos + length) else {
+            break; // truncated log
+        };
+        pos += length;
+
+        if msg_type == FMT_TYPE {
+            formats.insert(
+                payload[0],
+                Format {
+                    length: payload[1] as usize,
+                    name: c_string(&payload[2..6]),
+                    format: c_string(&payload[6..22]).into_bytes(),
+                    labels: c_string(&payload[22..86])
+                        .split(',')
+                        .map(|l| l.to_string())
+                        .collect(),
+                },
+            );
+            continue;
+        }
+
+        let format = &formats[&msg_type];
+        if !format.name.starts_with("IMU") && !format.name.starts_with("MAG") {
+            continue;
+        }
+
+        let values = decode(format, payload);
+        let time = format
+            .label_index("TimeUS")
+            .and_then(|i| values.get(i))
+            .map_or(0.0, |t| t * 1e-6);
+
+        if format.name.starts_with("IMU") {
+            let Some(i) = instance(format, &values, "IMU") else {
+                continue;
+            };
+            let temperature = format.label_index("T").and_then(|i| values.get(i).copied());
+            if let Some(gyro) = vector(format, &values, ["GyrX", "GyrY", "GyrZ"]) {
+                let name = format!("IMU[{}] gyro", i);
+                stream_mut(&mut streams, &name, SensorKind::Gyro)
+                    .samples
+                    .push(LogSample {
+                        time,
+                        value: gyro,
+                        temperature,
+                    });
+            }
+            if let Some(acc) = vector(format, &values, ["AccX", "AccY", "AccZ"]) {
+                let name = format!("IMU[{}] accel", i);
+                stream_mut(&mut streams, &name, SensorKind::Acc)
+                    .samples
+                    .push(LogSample {
+                        time,
+                        value: acc,
+                        temperature,
+                    });
+            }
+        } else {
+            let Some(i) = instance(format, &values, "MAG") else {
+                continue;
+            };
+            if let Some(field) = vector(format, &values, ["MagX", "MagY", "MagZ"]) {
+                // ArduPilot adds its current offsets and motor compensation before logging,
+                // undo both so the fit sees the uncorrected field. Older logs name the motor
+                // compensation MOfsX..Z. Scale corrections are not logged.
+                let offsets = vector(format, &values, ["OfsX", "OfsY", "OfsZ"]).unwrap_or_default();
+                let motor = vector(format, &values, ["MOX", "MOY", "MOZ"])
+                    .or_else(|| vector(format, &values, ["MOfsX", "MOfsY", "MOfsZ"]))
+                    .unwrap_or_default();
+                let name = format!("MAG[{}]", i);
+                stream_mut(&mut streams, &name, SensorKind::Mag)
+                    .samples
+                    .push(LogSample {
+                        time,
+                        value: field - offsets - motor,
+                        temperature: None,
+                    });
+            }
+        }
+    }
+
+    Ok(streams)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn fmt_message(msg_type: u8, name: &str, format: &str, labels: &str) -> Vec<u8> {
+        let length = 3 + format.bytes().map(field_size).sum::<usize>();
+
+        let mut msg = HEAD.to_vec();
+        msg.push(FMT_TYPE);
+        msg.push(msg_type);
+        msg.push(length as u8);
+        for (text, len) in [(name, 4), (format, 16), (labels, 64)] {
+            let mut field = text.as_bytes().to_vec();
+            field.resize(len, 0);
+            msg.extend(field);
+        }
+        msg
+    }
+
+    #[test]
+    fn parse_mag_instances() {
+        let mut log = fmt_message(
+            131,
+            "MAG",
+            "QBhhhhhhhhhBI",
+            "TimeUS,I,MagX,MagY,MagZ,OfsX,OfsY,OfsZ,MOX,MOY,MOZ,Health,S",
+        );
+        // Older firmware logs one message per instance and names the motor compensation MOfs.
+        log.extend(fmt_message(
+            132,
+            "MAG2",
+            "QhhhhhhhhhBI",
+            "TimeUS,MagX,MagY,MagZ,OfsX,OfsY,OfsZ,MOfsX,MOfsY,MOfsZ,Health,S",
+        ));
+
+        for (msg_type, instance, time) in [
+            (131, Some(0u8), 1_000_000u64),
+            (131, Some(1), 1_500_000),
+            (132, None, 2_000_000),
+        ] {
+            log.extend_from_slice(&HEAD);
+            log.push(msg_type);
+            log.extend_from_slice(&time.to_le_bytes());
+            log.extend(instance);
+            for v in [210i16, -50, 400, 10, 20, 30, 1, -2, 3] {
+                log.extend_from_slice(&v.to_le_bytes());
+            }
+            log.push(1);
+            log.extend_from_slice(&(time as u32).to_le_bytes());
+        }
+
+        let streams = parse(&log).unwrap();
+        assert_eq!(streams.len(), 2);
+        assert_eq!(streams[1].name, "MAG[1]");
+        assert_eq!(streams[1].samples.len(), 2);
+
+        for (sample, time) in streams[1].samples.iter().zip([1.5, 2.0]) {
+            assert!((sample.time - time).abs() < 1e-9);
+            assert!((sample.value - Vector3::new(199.0, -68.0, 367.0)).norm() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn instance_from_name() {
+        let format = |name: &str| Format {
+            name: name.to_string(),
+            length: 0,
+            format: vec![],
+            labels: vec![],
+        };
+        assert_eq!(instance(&format("IMU"), &[], "IMU"), Some(0));
+        assert_eq!(instance(&format("IMU2"), &[], "IMU"), Some(1));
+        assert_eq!(instance(&format("IMU0"), &[], "IMU"), None);
+    }
+}
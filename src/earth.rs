@@ -0,0 +1,131 @@
+// Earth rotation
+//
+// A resting gyro measures the Earth rate, 15°/h about the polar axis. In the local NED
+// frame it is
+//
+//   w_n = w_e * (cos(lat), 0, -sin(lat))
+//
+// and in the body frame it follows from the orientation, which is known from gravity
+// (accel) and the magnetic field (mag) when standing still. Conversely, the horizontal
+// part of the measured rate points north, which is what gyrocompassing uses.
+
+use nalgebra::{Matrix3, Rotation3, Vector3};
+
+pub const EARTH_RATE: f64 = 7.292115e-5; // rad/s, WGS84
+
+/// Earth rate in the local NED frame.
+pub fn earth_rate_ned(latitude: f64) -> Vector3<f64> {
+    EARTH_RATE * Vector3::new(latitude.cos(), 0.0, -latitude.sin())
+}
+
+/// Normal gravity (m/s²) of the WGS84 ellipsoid at the given latitude (rad) and height
+/// above the ellipsoid (m): Somigliana's formula with the second order height correction.
+///
+/// refs:
+/// NIMA TR8350.2, "Department of Defense World Geodetic System 1984", section 4.2
+pub fn normal_gravity(latitude: f64, height: f64) -> f64 {
+    const GAMMA_E: f64 = 9.7803253359; // m/s², at the equator
+    const K: f64 = 0.00193185265241; // Somigliana's constant
+    const E2: f64 = 0.00669437999013; // first eccentricity squared
+    const A: f64 = 6378137.0; // m, semi-major axis
+    const F: f64 = 1.0 / 298.257223563; // flattening
+    const M: f64 = 0.00344978650684; // w² a² b / GM
+
+    let sin2 = latitude.sin().powi(2);
+    let gamma = GAMMA_E * (1.0 + K * sin2) / (1.0 - E2 * sin2).sqrt();
+    gamma
+        * (1.0 - 2.0 / A * (1.0 + F + M - 2.0 * F * sin2) * height
+            + 3.0 * height * height / (A * A))
+}
+
+/// Rotation from NED to the body frame, from the accel (specific force, pointing up when
+/// at rest) and mag vectors. The declination (rad, positive east) turns magnetic into true
+/// north.
+pub fn ned_to_body(
+    acc: &Vector3<f64>,
+    mag: &Vector3<f64>,
+    declination: f64,
+) -> Option<Rotation3<f64>> {
+    let down = -acc.try_normalize(1e-9)?;
+    let east = down.cross(mag).try_normalize(1e-9)?;
+    let north = east.cross(&down);
+
+    // true north is the declination west of magnetic north
+    let magnetic = Rotation3::from_matrix_unchecked(Matrix3::from_columns(&[north, east, down]));
+    Some(magnetic * Rotation3::from_axis_angle(&Vector3::z_axis(), -declination))
+}
+
+/// Earth rate as seen by a resting IMU in the given orientation.
+pub fn earth_rate_body(
+    acc: &Vector3<f64>,
+    mag: &Vector3<f64>,
+    latitude: f64,
+    declination: f64,
+) -> Option<Vector3<f64>> {
+    Some(ned_to_body(acc, mag, declination)? * earth_rate_ned(latitude))
+}
+
+/// Heading of the body x axis from true north (rad, clockwise seen from above), from the
+/// bias free gyro and the accel vector of a resting IMU. Only works if gyro bias and noise
+/// are well below the horizontal Earth rate.
+pub fn gyrocompass(gyro: &Vector3<f64>, acc: &Vector3<f64>) -> Option<f64> {
+    let down = -acc.try_normalize(1e-9)?;
+    let north = (gyro - down * gyro.dot(&down)).try_normalize(1e-12)?;
+    let east = down.cross(&north);
+    Some(east.x.atan2(north.x))
+}
+
+/// Heading of the body x axis from true north (rad) from the accel and mag vectors.
+pub fn mag_heading(acc: &Vector3<f64>, mag: &Vector3<f64>, declination: f64) -> Option<f64> {
+    let body_to_ned = ned_to_body(acc, mag, declination)?.inverse();
+    let x = body_to_ned * Vector3::x();
+    Some(x.y.atan2(x.x))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn heading_rotation(heading: f64) -> Rotation3<f64> {
+        Rotation3::from_axis_angle(&Vector3::z_axis(), heading)
+    }
+
+    #[test]
+    fn earth_rate_and_heading() {
+        let latitude = 48f64.to_radians();
+        let declination = 3f64.to_radians();
+        let heading = 30f64.to_radians();
+
+        // body to NED: yaw by the heading, slightly tilted
+        let body_to_ned =
+            heading_rotation(heading) * Rotation3::from_euler_angles(0.05, -0.03, 0.0);
+        let ned_to_body_true = body_to_ned.inverse();
+
+        let gravity_up = ned_to_body_true * Vector3::new(0.0, 0.0, -9.81);
+        // magnetic north is the declination east of true north, dipping down
+        let field = heading_rotation(declination) * Vector3::new(20.0, 0.0, 40.0);
+        let mag = ned_to_body_true * field;
+        let gyro = ned_to_body_true * earth_rate_ned(latitude);
+
+        let rate = earth_rate_body(&gravity_up, &mag, latitude, declination).unwrap();
+        assert!((rate - gyro).norm() < 1e-12);
+
+        let compass = gyrocompass(&gyro, &gravity_up).unwrap();
+        let magnetic = mag_heading(&gravity_up, &mag, declination).unwrap();
+        let expected = (body_to_ned * Vector3::x())
+            .y
+            .atan2((body_to_ned * Vector3::x()).x);
+        assert!((compass - expected).abs() < 1e-9);
+        assert!((magnetic - expected).abs() < 1e-9);
+    }
+
+    #[test]
+    fn gravity() {
+        // equator and pole of the ellipsoid
+        assert!((normal_gravity(0.0, 0.0) - 9.7803253359).abs() < 1e-9);
+        assert!((normal_gravity(90f64.to_radians(), 0.0) - 9.8321849379).abs() < 1e-7);
+        // free-air gradient of about 3.086e-6 /s²
+        let gradient = (normal_gravity(0.8, 0.0) - normal_gravity(0.8, 1000.0)) / 1000.0;
+        assert!((gradient - 3.086e-6).abs() < 1e-8);
+    }
+}
@@ -0,0 +1,558 @@
+// Gyro scale and misalignment calibration
+//
+// The recording alternates between still periods and rotations. The bias is taken from
+// the still periods, each rotation in between yields a measured vector (integrated angle
+// or mean rate) and a reference vector along the dominant axis. The correction matrix G
+// maps measured onto reference vectors in the least squares sense: ref = G * meas.
+
+use crate::data_provider::SampleInfo;
+use nalgebra::{DMatrix, Matrix3, Rotation3, SVector, Vector3};
+use std::f64::consts::PI;
+
+const MOTION_THRESHOLD: f64 = 0.1; // rad/s, above the bias of consumer grade gyros
+const MIN_STILL_SAMPLES: usize = 20;
+const STILL_EDGE: usize = 5; // samples at both ends of a still period not used for the bias
+const MIN_G_POSES: usize = 6;
+const TURNTABLE_MARGIN: f64 = 0.1; // part of each rotation dropped at start and end (spin-up)
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GyroModel {
+    Offset,           // bias only, the device is expected to stand still
+    KnownTurns,       // rotations by a known number of full turns about each axis
+    Turntable,        // constant-rate rotations about each axis
+    GravityReference, // rotations between static poses, checked against the accel
+}
+
+impl GyroModel {
+    pub const ALL: [GyroModel; 4] = [
+        GyroModel::Offset,
+        GyroModel::KnownTurns,
+        GyroModel::Turntable,
+        GyroModel::GravityReference,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            GyroModel::Offset => "Offset",
+            GyroModel::KnownTurns => "Known turns",
+            GyroModel::Turntable => "Turntable",
+            GyroModel::GravityReference => "Gravity reference",
+        }
+    }
+}
+
+/// A rotation between two still periods, `start..end` indexes the samples.
+#[derive(Debug, Clone, Copy)]
+pub struct Motion {
+    pub start: usize,
+    pub end: usize,
+}
+
+/// Sample times in s, the device clock is preferred as it is not subject to buffering.
+pub fn sample_times(info: &[SampleInfo]) -> Option<Vec<f64>> {
+    info.iter()
+        .map(|i| i.device_time)
+        .collect::<Option<Vec<_>>>()
+        .or_else(|| info.iter().map(|i| i.host_time).collect())
+}
+
+/// Mean of the still samples (|ω| below the motion threshold for a while). The edges of
+/// the still periods are left out, they may contain the beginning of a slow rotation.
+pub fn still_bias(points: &[Vector3<f64>]) -> Option<Vector3<f64>> {
+    let still = still_mask(points, &Vector3::zeros(), STILL_EDGE);
+    let (sum, count) = points
+        .iter()
+        .zip(&still)
+        .filter(|(_, s)| **s)
+        .fold((Vector3::zeros(), 0), |(sum, count), (p, _)| {
+            (sum + p, count + 1)
+        });
+    (count > 0).then(|| sum / count as f64)
+}
+
+fn still_mask(points: &[Vector3<f64>], bias: &Vector3<f64>, edge: usize) -> Vec<bool> {
+    let mut mask = vec![false; points.len()];
+    let mut start = 0;
+    for i in 0..=points.len() {
+        let still = i < points.len() && (points[i] - bias).norm() < MOTION_THRESHOLD;
+        if !still {
+            if i - start >= MIN_STILL_SAMPLES {
+                mask[start + edge..i - edge].fill(true);
+            }
+            start = i + 1;
+        }
+    }
+    mask
+}
+
+/// Rotations enclosed by still periods.
+pub fn find_motions(points: &[Vector3<f64>], bias: &Vector3<f64>) -> Vec<Motion> {
+    let still = still_mask(points, bias, 0);
+    let mut motions = vec![];
+    let mut start = None;
+
+    for i in 1..still.len() {
+        if still[i - 1] && !still[i] {
+            start = Some(i);
+        } else if !still[i - 1] && still[i] {
+            if let Some(start) = start.take() {
+                motions.push(Motion { start, end: i });
+            }
+        }
+    }
+
+    motions
+}
+
+/// Integrated, bias-corrected rate over the motion in rad.
+pub fn integrate(
+    points: &[Vector3<f64>],
+    times: &[f64],
+    bias: &Vector3<f64>,
+    motion: &Motion,
+) -> Vector3<f64> {
+    (motion.start..motion.end)
+        .map(|i| (points[i] - bias) * (times[i] - times[i - 1]))
+        .sum()
+}
+
+/// Vector along the dominant axis of `v` with the given magnitude.
+fn dominant_axis(v: &Vector3<f64>, magnitude: f64) -> Vector3<f64> {
+    let axis = v.iamax();
+    let mut r = Vector3::zeros();
+    r[axis] = magnitude * v[axis].signum();
+    r
+}
+
+/// G minimizing |ref - G * meas|² over all pairs, `None` unless all axes were excited.
+pub fn solve_correction(pairs: &[(Vector3<f64>, Vector3<f64>)]) -> Option<Matrix3<f64>> {
+    let mut mm = Matrix3::zeros();
+    let mut rm = Matrix3::zeros();
+    for (meas, reference) in pairs {
+        mm += meas * meas.transpose();
+        rm += reference * meas.transpose();
+    }
+    Some(rm * mm.try_inverse()?)
+}
+
+/// Bias and correction matrix from rotations by `turns` full turns each.
+pub fn calibrate_known_turns(
+    points: &[Vector3<f64>],
+    info: &[SampleInfo],
+    turns: f64,
+) -> Option<(Vector3<f64>, Matrix3<f64>)> {
+    let times = sample_times(info)?;
+    let bias = still_bias(points)?;
+    let pairs: Vec<_> = find_motions(points, &bias)
+        .iter()
+        .map(|m| {
+            let angle = integrate(points, &times, &bias, m);
+            (angle, dominant_axis(&angle, 2.0 * PI * turns))
+        })
+        .collect();
+    Some((bias, solve_correction(&pairs)?))
+}
+
+/// Bias and correction matrix from constant-rate rotations at `rate` rad/s.
+pub fn calibrate_turntable(
+    points: &[Vector3<f64>],
+    rate: f64,
+) -> Option<(Vector3<f64>, Matrix3<f64>)> {
+    let bias = still_bias(points)?;
+    let pairs: Vec<_> = find_motions(points, &bias)
+        .iter()
+        .filter_map(|m| {
+            let margin = ((m.end - m.start) as f64 * TURNTABLE_MARGIN) as usize;
+            let samples = &points[m.start + margin..m.end - margin];
+            if samples.is_empty() {
+                return None;
+            }
+            let mean = samples.iter().sum::<Vector3<f64>>() / samples.len() as f64 - bias;
+            Some((mean, dominant_axis(&mean, rate)))
+        })
+        .collect();
+    Some((bias, solve_correction(&pairs)?))
+}
+
+/// A rotation between two static poses together with the gravity directions (unit
+/// vectors, from the calibrated accel) before and after.
+struct GravityStep {
+    motion: Motion,
+    before: Vector3<f64>,
+    after: Vector3<f64>,
+}
+
+/// Gravity direction after the motion as predicted by the gyro, corrected by `g`.
+fn predict_gravity(
+    step: &GravityStep,
+    points: &[Vector3<f64>],
+    times: &[f64],
+    bias: &Vector3<f64>,
+    g: &Matrix3<f64>,
+) -> Vector3<f64> {
+    let mut rotation = Rotation3::identity();
+    for i in step.motion.start..step.motion.end {
+        let w = g * (points[i] - bias);
+        rotation *= Rotation3::new(w * (times[i] - times[i - 1]));
+    }
+    // a vector fixed in the world, seen from the rotated body
+    rotation.inverse() * step.before
+}
+
+/// Bias and correction matrix from rotations between static poses. The gravity direction
+/// measured by the (calibrated) accel before and after each rotation is the reference the
+/// integrated gyro has to match. Levenberg-Marquardt over the 9 entries of G, starting
+/// from the identity. Rotations about the vertical axis do not contribute, so the device
+/// has to be tilted in many different ways.
+///
+/// refs:
+/// D. Tedaldi, A. Pretto, E. Menegatti, "A robust and easy to implement method for IMU
+/// calibration without external equipments", ICRA 2014
+pub fn calibrate_gravity_reference(
+    gyro_points: &[Vector3<f64>],
+    gyro_info: &[SampleInfo],
+    acc_points: &[Vector3<f64>],
+    acc_info: &[SampleInfo],
+    acc_cal: impl Fn(&Vector3<f64>) -> Vector3<f64>,
+) -> Option<(Vector3<f64>, Matrix3<f64>)> {
+    let times = sample_times(gyro_info)?;
+    let acc_times = sample_times(acc_info)?;
+    let bias = still_bias(gyro_points)?;
+
+    // mean accel direction within a time span
+    let gravity = |from: f64, to: f64| {
+        let sum: Vector3<f64> = acc_points
+            .iter()
+            .zip(&acc_times)
+            .filter(|(_, t)| **t >= from && **t <= to)
+            .map(|(a, _)| acc_cal(a))
+            .sum();
+        (sum.norm() > 0.0).then(|| sum.normalize())
+    };
+
+    // still periods around each motion
+    let motions = find_motions(gyro_points, &bias);
+    let mut steps = vec![];
+    for (i, motion) in motions.iter().enumerate() {
+        let still_start = if i == 0 { 0 } else { motions[i - 1].end };
+        let still_end = motions.get(i + 1).map_or(gyro_points.len(), |m| m.start);
+        let (Some(before), Some(after)) = (
+            gravity(times[still_start], times[motion.start - 1]),
+            gravity(times[motion.end], times[still_end - 1]),
+        ) else {
+            continue;
+        };
+        steps.push(GravityStep {
+            motion: *motion,
+            before,
+            after,
+        });
+    }
+    if steps.len() < 5 {
+        return None;
+    }
+
+    let residuals = |x: &SVector<f64, 9>| -> Vec<f64> {
+        let g = Matrix3::from_row_slice(x.as_slice());
+        steps
+            .iter()
+            .flat_map(|step| {
+                let r = predict_gravity(step, gyro_points, &times, &bias, &g) - step.after;
+                [r.x, r.y, r.z]
+            })
+            .collect()
+    };
+    let cost = |r: &[f64]| r.iter().map(|r| r * r).sum::<f64>();
+
+    let mut x =
+        SVector::<f64, 9>::from_row_slice(Matrix3::<f64>::identity().transpose().as_slice());
+    let mut r = residuals(&x);
+    let mut lambda = 1e-3;
+
+    for _ in 0..50 {
+        // numerical jacobian, the analytical one of the rotation product is not worth it
+        let h = 1e-7;
+        let mut jtj = nalgebra::SMatrix::<f64, 9, 9>::zeros();
+        let mut jtr = SVector::<f64, 9>::zeros();
+        let columns: Vec<Vec<f64>> = (0..9)
+            .map(|k| {
+                let mut xh = x;
+                xh[k] += h;
+                residuals(&xh)
+                    .iter()
+                    .zip(&r)
+                    .map(|(rh, r)| (rh - r) / h)
+                    .collect()
+            })
+            .collect();
+        for a in 0..9 {
+            jtr[a] = columns[a].iter().zip(&r).map(|(j, r)| j * r).sum();
+            for b in 0..9 {
+                jtj[(a, b)] = columns[a]
+                    .iter()
+                    .zip(&columns[b])
+                    .map(|(ja, jb)| ja * jb)
+                    .sum();
+            }
+        }
+
+        let mut damped = jtj;
+        for i in 0..9 {
+            damped[(i, i)] += lambda * jtj[(i, i)].max(1e-12);
+        }
+        let step = damped.cholesky()?.solve(&-jtr);
+        let candidate = x + step;
+        let candidate_r = residuals(&candidate);
+
+        if cost(&candidate_r) < cost(&r) {
+            x = candidate;
+            r = candidate_r;
+            lambda *= 0.1;
+            if step.norm() < 1e-10 {
+                break;
+            }
+        } else {
+            lambda *= 10.0;
+        }
+    }
+
+    Some((bias, Matrix3::from_row_slice(x.as_slice())))
+}
+
+/// Static poses as (mean gyro, mean calibrated accel) of each still period.
+fn static_poses(
+    gyro_points: &[Vector3<f64>],
+    times: &[f64],
+    acc_points: &[Vector3<f64>],
+    acc_times: &[f64],
+    acc_cal: impl Fn(&Vector3<f64>) -> Vector3<f64>,
+) -> Vec<(Vector3<f64>, Vector3<f64>)> {
+    let still = still_mask(gyro_points, &Vector3::zeros(), STILL_EDGE);
+    let mut poses = vec![];
+    let mut start = None;
+    for i in 0..=still.len() {
+        match (start, i < still.len() && still[i]) {
+            (None, true) => start = Some(i),
+            (Some(s), false) => {
+                start = None;
+                let gyro = gyro_points[s..i].iter().sum::<Vector3<f64>>() / (i - s) as f64;
+                let acc: Vec<_> = acc_points
+                    .iter()
+                    .zip(acc_times)
+                    .filter(|(_, t)| **t >= times[s] && **t <= times[i - 1])
+                    .map(|(a, _)| acc_cal(a))
+                    .collect();
+                if !acc.is_empty() {
+                    poses.push((gyro, acc.iter().sum::<Vector3<f64>>() / acc.len() as f64));
+                }
+            }
+            _ => (),
+        }
+    }
+    poses
+}
+
+/// Bias and g-sensitivity S of the gyro, gyro = bias + S * a while still, from still
+/// periods in different orientations. S is in (rad/s) / (m/s²). The Earth rate, which
+/// also changes with the orientation, is well below the g-sensitivity of the gyros this
+/// is meant for.
+pub fn calibrate_g_sensitivity(
+    gyro_points: &[Vector3<f64>],
+    gyro_info: &[SampleInfo],
+    acc_points: &[Vector3<f64>],
+    acc_info: &[SampleInfo],
+    acc_cal: impl Fn(&Vector3<f64>) -> Vector3<f64>,
+) -> Option<(Vector3<f64>, Matrix3<f64>)> {
+    let times = sample_times(gyro_info)?;
+    let acc_times = sample_times(acc_info)?;
+    let poses = static_poses(gyro_points, &times, acc_points, &acc_times, acc_cal);
+    if poses.len() < MIN_G_POSES {
+        return None;
+    }
+
+    // rows [1, ax, ay, az], one column of coefficients per gyro axis
+    let a = DMatrix::from_fn(poses.len(), 4, |r, c| match c {
+        0 => 1.0,
+        _ => poses[r].1[c - 1],
+    });
+    let b = DMatrix::from_fn(poses.len(), 3, |r, c| poses[r].0[c]);
+    let svd = a.svd(true, true);
+    let singular = &svd.singular_values;
+    if singular.min() < 1e-3 * singular.max() {
+        return None; // the gravity directions do not span all axes
+    }
+    let x = svd.solve(&b, 1e-12).ok()?;
+
+    let bias = Vector3::new(x[(0, 0)], x[(0, 1)], x[(0, 2)]);
+    let sensitivity = Matrix3::from_fn(|r, c| x[(c + 1, r)]);
+    Some((bias, sensitivity))
+}
+
+/// Splits a correction matrix into the rotation from the gyro into the accel frame and
+/// the symmetric scale/non-orthogonality part: G = R * S (polar decomposition). Only
+/// meaningful if G was calibrated against the accel. A mirroring G (negative determinant,
+/// e.g. a wrong axis map) still gives a proper rotation, the sign flip of the weakest
+/// axis goes into S, which then has a negative eigenvalue.
+pub fn split_frame_rotation(g: &Matrix3<f64>) -> (Rotation3<f64>, Matrix3<f64>) {
+    let svd = g.svd(true, true);
+    let (u, v_t) = (svd.u.unwrap(), svd.v_t.unwrap());
+    let mut d = Vector3::repeat(1.0);
+    if (u * v_t).determinant() < 0.0 {
+        d[svd.singular_values.imin()] = -1.0;
+    }
+    let r = u * Matrix3::from_diagonal(&d) * v_t;
+    let s = v_t.transpose() * Matrix3::from_diagonal(&d.component_mul(&svd.singular_values)) * v_t;
+    (Rotation3::from_matrix_unchecked(r), s)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn known_turns() {
+        let g = Matrix3::new(1.03, 0.01, -0.02, 0.005, 0.98, 0.01, -0.01, 0.02, 1.01);
+        let g_inv = g.try_inverse().unwrap();
+        let bias = Vector3::new(0.01, -0.02, 0.005);
+
+        let mut points = vec![];
+        let mut info = vec![];
+        let dt = 0.01;
+        let mut push = |w: Vector3<f64>| {
+            info.push(SampleInfo {
+                device_time: Some(info.len() as f64 * dt),
+                ..Default::default()
+            });
+            points.push(g_inv * w + bias);
+        };
+
+        for axis in 0..3 {
+            for _ in 0..50 {
+                push(Vector3::zeros());
+            }
+            // one turn in 2 s, accelerating and braking
+            let mut w = Vector3::zeros();
+            for i in 0..200 {
+                w[axis] = PI * (PI * i as f64 / 200.0).sin() * PI / 2.0
+                    * if axis == 1 { -1.0 } else { 1.0 };
+                push(w);
+            }
+        }
+        for _ in 0..50 {
+            push(Vector3::zeros());
+        }
+
+        let (b, g_est) = calibrate_known_turns(&points, &info, 1.0).unwrap();
+        assert!((b - bias).norm() < 1e-9);
+        assert!((g_est - g).norm() < 0.01, "{}", g_est);
+    }
+
+    #[test]
+    fn frame_rotation() {
+        let r = Rotation3::from_euler_angles(0.01, -0.005, 0.02);
+        let s = Matrix3::new(1.02, 0.01, 0.0, 0.01, 0.99, -0.005, 0.0, -0.005, 1.01);
+        let (r_est, s_est) = split_frame_rotation(&(r * s));
+        assert!((r_est.matrix() - r.matrix()).norm() < 1e-9);
+        assert!((s_est - s).norm() < 1e-9);
+
+        // mirrored z axis
+        let g = r * s * Matrix3::from_diagonal(&Vector3::new(1.0, 1.0, -1.0));
+        let (r_est, s_est) = split_frame_rotation(&g);
+        assert!((r_est.matrix().determinant() - 1.0).abs() < 1e-9);
+        assert!((r_est.matrix() * s_est - g).norm() < 1e-9);
+        assert!(s_est.determinant() < 0.0);
+    }
+
+    #[test]
+    fn gravity_reference() {
+        let g = Matrix3::new(1.03, 0.01, -0.02, 0.005, 0.98, 0.01, -0.01, 0.02, 1.01);
+        let g_inv = g.try_inverse().unwrap();
+        let bias = Vector3::new(0.01, -0.02, 0.005);
+
+        let mut gyro = vec![];
+        let mut acc = vec![];
+        let mut info = vec![];
+        let dt = 0.01;
+        let mut gravity = Vector3::new(0.0, 0.0, 9.81);
+
+        let axes = [
+            Vector3::new(1.0, 0.2, 0.0),
+            Vector3::new(0.1, 1.0, 0.3),
+            Vector3::new(-0.5, 0.3, 1.0),
+            Vector3::new(0.7, -0.7, 0.2),
+            Vector3::new(0.2, 0.4, -0.9),
+            Vector3::new(-1.0, 0.1, 0.5),
+            Vector3::new(0.3, -1.0, -0.4),
+            Vector3::new(0.6, 0.6, 0.6),
+        ];
+        for step in 0..=axes.len() {
+            for _ in 0..50 {
+                info.push(SampleInfo {
+                    device_time: Some(info.len() as f64 * dt),
+                    ..Default::default()
+                });
+                gyro.push(g_inv * Vector3::zeros() + bias);
+                acc.push(gravity);
+            }
+            let Some(axis) = axes.get(step) else {
+                break;
+            };
+            for i in 0..100 {
+                let w = axis.normalize() * 1.5 * (PI * i as f64 / 100.0).sin();
+                info.push(SampleInfo {
+                    device_time: Some(info.len() as f64 * dt),
+                    ..Default::default()
+                });
+                gyro.push(g_inv * w + bias);
+                gravity = Rotation3::new(w * dt).inverse() * gravity;
+                acc.push(gravity);
+            }
+        }
+
+        let (b, g_est) = calibrate_gravity_reference(&gyro, &info, &acc, &info, |a| *a).unwrap();
+        assert!((b - bias).norm() < 1e-9);
+        assert!((g_est - g).norm() < 0.01, "{}", g_est);
+    }
+
+    #[test]
+    fn g_sensitivity() {
+        let bias = Vector3::new(0.01, -0.02, 0.005);
+        let sensitivity = Matrix3::new(2e-4, 1e-5, 0.0, -3e-5, 1e-4, 2e-5, 0.0, 5e-5, -1e-4);
+
+        let mut gyro = vec![];
+        let mut acc = vec![];
+        let mut info = vec![];
+        let poses = [
+            Vector3::new(0.0, 0.0, 9.81),
+            Vector3::new(0.0, 0.0, -9.81),
+            Vector3::new(9.81, 0.0, 0.0),
+            Vector3::new(-9.81, 0.0, 0.0),
+            Vector3::new(0.0, 9.81, 0.0),
+            Vector3::new(0.0, -9.81, 0.0),
+            Vector3::new(5.66, 5.66, 5.66),
+        ];
+        for a in poses {
+            for _ in 0..50 {
+                info.push(SampleInfo {
+                    device_time: Some(info.len() as f64 * 0.01),
+                    ..Default::default()
+                });
+                gyro.push(bias + sensitivity * a);
+                acc.push(a);
+            }
+            // turning to the next pose
+            for _ in 0..20 {
+                info.push(SampleInfo {
+                    device_time: Some(info.len() as f64 * 0.01),
+                    ..Default::default()
+                });
+                gyro.push(Vector3::new(1.0, 0.5, 0.0));
+                acc.push(Vector3::zeros());
+            }
+        }
+
+        let (b, s) = calibrate_g_sensitivity(&gyro, &info, &acc, &info, |a| *a).unwrap();
+        assert!((b - bias).norm() < 1e-9);
+        assert!((s - sensitivity).norm() < 1e-9);
+    }
+}
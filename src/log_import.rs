@@ -0,0 +1,183 @@
+use crate::cal::Cal;
+use crate::data_provider::SampleInfo;
+use crate::units::InputUnits;
+use nalgebra::Vector3;
+use std::path::Path;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SensorKind {
+    Gyro,
+    Acc,
+    Mag,
+}
+
+impl SensorKind {
+    pub const ALL: [SensorKind; 3] = [SensorKind::Gyro, SensorKind::Acc, SensorKind::Mag];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            SensorKind::Gyro => "Gyro",
+            SensorKind::Acc => "Accel",
+            SensorKind::Mag => "Mag",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct LogSample {
+    pub time: f64, // s since boot
+    pub value: Vector3<f64>,
+    pub temperature: Option<f64>, // °C
+}
+
+/// One sensor instance found in a flight log, e.g. `sensor_mag[1]` or `IMU[0]`.
+#[derive(Debug)]
+pub struct LogStream {
+    pub name: String,
+    pub kind: SensorKind,
+    pub samples: Vec<LogSample>,
+    pub selected: bool,
+}
+
+impl LogStream {
+    pub fn new(name: String, kind: SensorKind) -> Self {
+        Self {
+            name,
+            kind,
+            samples: vec![],
+            selected: false,
+        }
+    }
+
+    pub fn duration(&self) -> f64 {
+        match (self.samples.first(), self.samples.last()) {
+            (Some(first), Some(last)) => last.time - first.time,
+            _ => 0.0,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct LogImport {
+    pub file_name: String,
+    pub streams: Vec<LogStream>,
+    pub units: InputUnits,
+}
+
+impl LogImport {
+    /// Loads a PX4 ULog (`.ulg`) or ArduPilot DataFlash (`.bin`) log.
+    pub fn load(path: &Path) -> std::io::Result<Self> {
+        let data = std::fs::read(path)?;
+
+        let (mut streams, units) = match path.extension().and_then(|e| e.to_str()) {
+            Some("ulg") => (crate::ulog::parse(&data)?, crate::ulog::UNITS),
+            Some("bin") | Some("BIN") => (crate::dataflash::parse(&data)?, crate::dataflash::UNITS),
+            _ => {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidInput,
+                    "Unsupported log format",
+                ))
+            }
+        };
+
+        streams.retain(|s| !s.samples.is_empty());
+        streams.sort_by(|a, b| a.name.cmp(&b.name));
+
+        Ok(Self {
+            file_name: path
+                .file_name()
+                .map_or(String::new(), |n| n.to_string_lossy().to_string()),
+            streams,
+            units,
+        })
+    }
+
+    /// Adds the selected streams to the calibration, scaled to SI units and mapped onto the
+    /// body frame like live data.
+    pub fn import_into(&self, cal: &mut Cal) {
+        let maps = cal.axis_maps;
+        for stream in self.streams.iter().filter(|s| s.selected) {
+            for sample in &stream.samples {
+                let info = SampleInfo {
+                    device_time: Some(sample.time),
+                    temperature: sample.temperature,
+                    ..Default::default()
+                };
+                match stream.kind {
+                    SensorKind::Gyro => {
+                        let value = maps.gyro.apply(sample.value * self.units.gyro_factor());
+                        cal.add_gyro_measurement(value, info)
+                    }
+                    SensorKind::Acc => {
+                        let value = maps.acc.apply(sample.value * self.units.acc_factor());
+                        cal.add_acc_measurement(value, info)
+                    }
+                    SensorKind::Mag => {
+                        let value = maps.mag.apply(sample.value * self.units.mag_factor());
+                        cal.add_mag_measurement(value, info)
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Returns the stream with the given name, creating it if it does not exist yet.
+pub fn stream_mut<'a>(
+    streams: &'a mut Vec<LogStream>,
+    name: &str,
+    kind: SensorKind,
+) -> &'a mut LogStream {
+    let index = match streams.iter().position(|s| s.name == name) {
+        Some(index) => index,
+        None => {
+            streams.push(LogStream::new(name.to_string(), kind));
+            streams.len() - 1
+        }
+    };
+    &mut streams[index]
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::axis_map::{Axis, AxisMap};
+    use crate::units::{AccUnit, MagUnit};
+
+    #[test]
+    fn import_applies_axis_maps() {
+        let mut streams = vec![];
+        for (name, kind) in [
+            ("gyro", SensorKind::Gyro),
+            ("acc", SensorKind::Acc),
+            ("mag", SensorKind::Mag),
+        ] {
+            let stream = stream_mut(&mut streams, name, kind);
+            stream.selected = true;
+            stream.samples.push(LogSample {
+                time: 1.0,
+                value: Vector3::new(1.0, 2.0, 3.0),
+                temperature: None,
+            });
+        }
+        let mut units = InputUnits::with_mag(MagUnit::Gauss);
+        units.acc = AccUnit::G;
+        let log = LogImport {
+            file_name: String::new(),
+            streams,
+            units,
+        };
+
+        let mut cal = Cal::new();
+        let map = AxisMap::new(Axis::PosY, Axis::PosX, Axis::NegZ);
+        cal.axis_maps.gyro = map;
+        cal.axis_maps.acc = map;
+        cal.axis_maps.mag = map;
+        log.import_into(&mut cal);
+
+        let mapped = Vector3::new(2.0, 1.0, -3.0);
+        assert_eq!(cal.gyro_measurements()[0], mapped);
+        assert!((cal.acc_measurements()[0] - mapped * 9.80665).norm() < 1e-9);
+        assert!((cal.mag_measurements()[0] - mapped * 100.0).norm() < 1e-9);
+    }
+}
@@ -2,13 +2,31 @@
 
 use serial_data_provider::SerialDataProvider;
 
+mod alignment;
+mod allan;
+mod axis_map;
 mod cal;
 mod data_provider;
+mod dataflash;
+mod earth;
+mod gyro_cal;
+mod log_import;
+mod poses;
+mod profiles;
+mod psd;
 mod quality;
 #[cfg(feature = "ros")]
 mod ros_data_provider;
 mod serial_data_provider;
+mod serial_protocol;
+mod stream_health;
+mod temp_comp;
 mod ui;
+mod ulog;
+mod units;
+mod warmup;
+mod wizard;
+mod wmm;
 
 fn main() {
     let rt = tokio::runtime::Runtime::new().expect("Unable to create Runtime");
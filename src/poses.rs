@@ -0,0 +1,117 @@
+// Static pose detection for the accelerometer calibration
+//
+// The accel samples are split into still periods, each one is averaged to a single pose
+// vector. The fit then weights every orientation alike, no matter how long it was held.
+
+use crate::axis_map::Axis;
+use nalgebra::Vector3;
+use std::ops::Range;
+
+const POSE_TOLERANCE: f64 = 0.2; // m/s², max. deviation from the running mean (~1° tilt)
+const MIN_POSE_SAMPLES: usize = 20;
+const DISTINCT_ANGLE: f64 = 10.0; // °, poses closer than this count as one
+const COVERAGE_ANGLE: f64 = 45.0; // °, a direction is covered by poses within this angle
+
+#[derive(Debug, Clone, Copy)]
+pub struct Pose {
+    pub mean: Vector3<f64>,
+}
+
+/// Sample ranges of the still periods, motion in between is dropped.
+pub fn static_ranges(points: &[Vector3<f64>]) -> Vec<Range<usize>> {
+    let mut ranges = vec![];
+    let mut start = 0;
+
+    while start < points.len() {
+        let mut sum = points[start];
+        let mut end = start + 1;
+        while end < points.len() {
+            let mean = sum / (end - start) as f64;
+            if (points[end] - mean).norm() > POSE_TOLERANCE {
+                break;
+            }
+            sum += points[end];
+            end += 1;
+        }
+
+        if end - start >= MIN_POSE_SAMPLES {
+            ranges.push(start..end);
+        }
+        start = end;
+    }
+
+    ranges
+}
+
+/// Splits the samples into still periods, averaged to one vector each.
+pub fn segment_static(points: &[Vector3<f64>]) -> Vec<Pose> {
+    static_ranges(points)
+        .into_iter()
+        .map(|r| Pose {
+            mean: points[r.clone()].iter().sum::<Vector3<f64>>() / r.len() as f64,
+        })
+        .collect()
+}
+
+/// Number of poses pointing in clearly different directions.
+pub fn distinct_count(poses: &[Pose]) -> usize {
+    let mut directions: Vec<Vector3<f64>> = vec![];
+    for pose in poses {
+        let dir = pose.mean.normalize();
+        if directions
+            .iter()
+            .all(|d| d.angle(&dir).to_degrees() > DISTINCT_ANGLE)
+        {
+            directions.push(dir);
+        }
+    }
+    directions.len()
+}
+
+/// Axis directions (±X, ±Y, ±Z pointing up) no pose is close to.
+pub fn missing_directions(poses: &[Pose]) -> Vec<Axis> {
+    Axis::ALL
+        .into_iter()
+        .filter(|axis| {
+            poses
+                .iter()
+                .all(|p| p.mean.angle(&axis.unit_vector()).to_degrees() > COVERAGE_ANGLE)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn segments() {
+        let mut points = vec![];
+        for (i, g) in [
+            Vector3::new(0.0, 0.0, 9.81),
+            Vector3::new(9.81, 0.0, 0.0),
+            Vector3::new(0.0, 0.0, 9.81),
+        ]
+        .iter()
+        .enumerate()
+        {
+            for j in 0..50 {
+                let noise = if j % 2 == 0 { 0.02 } else { -0.02 };
+                points.push(g + Vector3::repeat(noise));
+            }
+            // motion in between
+            for j in 0..5 {
+                points.push(Vector3::new(5.0 + i as f64, j as f64, 3.0));
+            }
+        }
+
+        let poses = segment_static(&points);
+        assert_eq!(poses.len(), 3);
+        assert!((poses[1].mean - Vector3::new(9.81, 0.0, 0.0)).norm() < 1e-3);
+        assert_eq!(distinct_count(&poses), 2);
+        assert_eq!(
+            missing_directions(&poses),
+            vec![Axis::NegX, Axis::PosY, Axis::NegY, Axis::NegZ]
+        );
+    }
+}
@@ -0,0 +1,127 @@
+// Datasheet values of common IMU chips
+//
+// The scales assume raw register values at the power-on full scale range (or the range
+// noted), the mag axis map brings the magnetometer into the accel/gyro frame.
+
+use crate::axis_map::{Axis, AxisMap};
+use crate::units::{AccUnit, GyroUnit, InputUnits, MagUnit};
+
+const G0: f64 = 9.80665;
+const BANDWIDTH: f64 = 100.0; // Hz, assumed for the still thresholds
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SensorProfile {
+    pub name: &'static str,
+    pub units: InputUnits,
+    pub mag_axis_map: AxisMap,
+    pub acc_noise: f64,  // µg/√Hz
+    pub gyro_noise: f64, // °/s/√Hz
+}
+
+impl SensorProfile {
+    /// 3σ of the noise vector norm within the assumed bandwidth in m/s².
+    pub fn acc_still_threshold(&self) -> f64 {
+        3.0 * (3.0 * BANDWIDTH).sqrt() * self.acc_noise * 1e-6 * G0
+    }
+
+    /// 3σ of the noise vector norm within the assumed bandwidth in rad/s.
+    pub fn gyro_still_threshold(&self) -> f64 {
+        3.0 * (3.0 * BANDWIDTH).sqrt() * self.gyro_noise.to_radians()
+    }
+}
+
+const fn raw_units(acc_lsb: f64, gyro_lsb: f64, mag: MagUnit, mag_lsb: f64) -> InputUnits {
+    InputUnits {
+        acc: AccUnit::G,
+        acc_lsb,
+        gyro: GyroUnit::DegPerSecond,
+        gyro_lsb,
+        mag,
+        mag_lsb,
+    }
+}
+
+pub const PROFILES: [SensorProfile; 8] = [
+    SensorProfile {
+        name: "MPU-6050",
+        units: raw_units(1.0 / 16384.0, 1.0 / 131.0, MagUnit::MicroTesla, 1.0), // ±2 g, ±250 °/s
+        mag_axis_map: AxisMap::new(Axis::PosX, Axis::PosY, Axis::PosZ),         // no mag
+        acc_noise: 400.0,
+        gyro_noise: 0.005,
+    },
+    SensorProfile {
+        name: "MPU-9250 (AK8963)",
+        units: raw_units(1.0 / 16384.0, 1.0 / 131.0, MagUnit::MicroTesla, 0.15), // 16 bit mag
+        mag_axis_map: AxisMap::new(Axis::PosY, Axis::PosX, Axis::NegZ),
+        acc_noise: 300.0,
+        gyro_noise: 0.01,
+    },
+    SensorProfile {
+        name: "ICM-20948 (AK09916)",
+        units: raw_units(1.0 / 16384.0, 1.0 / 131.0, MagUnit::MicroTesla, 0.15),
+        mag_axis_map: AxisMap::new(Axis::PosX, Axis::NegY, Axis::NegZ),
+        acc_noise: 230.0,
+        gyro_noise: 0.015,
+    },
+    SensorProfile {
+        name: "LSM9DS1",
+        units: raw_units(0.061e-3, 8.75e-3, MagUnit::MilliGauss, 0.14), // ±2 g, ±245 °/s, ±4 G
+        mag_axis_map: AxisMap::new(Axis::NegX, Axis::PosY, Axis::PosZ), // mag X is reversed
+        acc_noise: 90.0,
+        gyro_noise: 0.008,
+    },
+    SensorProfile {
+        name: "LSM6DSOX + LIS3MDL",
+        units: raw_units(0.061e-3, 8.75e-3, MagUnit::Gauss, 1.0 / 6842.0), // ±4 G
+        mag_axis_map: AxisMap::new(Axis::PosX, Axis::PosY, Axis::PosZ),    // separate chips
+        acc_noise: 70.0,
+        gyro_noise: 0.0038,
+    },
+    SensorProfile {
+        name: "LSM6DS3 + LIS3MDL",
+        units: raw_units(0.061e-3, 8.75e-3, MagUnit::Gauss, 1.0 / 6842.0),
+        mag_axis_map: AxisMap::new(Axis::PosX, Axis::PosY, Axis::PosZ),
+        acc_noise: 90.0,
+        gyro_noise: 0.007,
+    },
+    SensorProfile {
+        name: "BMI088 + BMM150",
+        // ±6 g (ACC_RANGE reset value 0x01), ±2000 °/s (power-on), compensated BMM150
+        // output in 1/16 µT
+        units: raw_units(1.0 / 5460.0, 1.0 / 16.384, MagUnit::MicroTesla, 1.0 / 16.0),
+        mag_axis_map: AxisMap::new(Axis::PosX, Axis::PosY, Axis::PosZ),
+        acc_noise: 175.0,
+        gyro_noise: 0.014,
+    },
+    SensorProfile {
+        name: "BNO055",
+        // raw data registers in m/s² (100 LSB), °/s (16 LSB) and µT (16 LSB), axes already
+        // aligned by the chip
+        units: InputUnits {
+            acc: AccUnit::MeterPerSecond2,
+            acc_lsb: 0.01,
+            gyro: GyroUnit::DegPerSecond,
+            gyro_lsb: 1.0 / 16.0,
+            mag: MagUnit::MicroTesla,
+            mag_lsb: 1.0 / 16.0,
+        },
+        mag_axis_map: AxisMap::new(Axis::PosX, Axis::PosY, Axis::PosZ),
+        acc_noise: 150.0,
+        gyro_noise: 0.014,
+    },
+];
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn thresholds() {
+        let mpu = PROFILES[0];
+        assert!((mpu.units.acc_factor() * 16384.0 - G0).abs() < 1e-9);
+        // a few ten mg and a few m°/s for a consumer grade IMU
+        assert!(mpu.acc_still_threshold() > 0.05 && mpu.acc_still_threshold() < 0.5);
+        assert!(mpu.gyro_still_threshold() > 1e-3 && mpu.gyro_still_threshold() < 1e-2);
+        assert!(PROFILES.iter().all(|p| p.mag_axis_map.is_permutation()));
+    }
+}
@@ -0,0 +1,213 @@
+// Noise power spectral density
+//
+// Welch's method: the samples are split into half-overlapping segments, each one is
+// detrended, Hann windowed and transformed, the periodograms are averaged. The result is
+// the one-sided PSD in unit²/Hz, its square root the noise density in unit/√Hz. Only
+// segments within still periods are used, the motion between poses is no vibration.
+//
+// refs:
+// https://en.wikipedia.org/wiki/Welch%27s_method
+
+use crate::allan;
+use crate::data_provider::SampleInfo;
+use nalgebra::Vector3;
+use std::f64::consts::PI;
+use std::ops::Range;
+
+const PEAK_FACTOR: f64 = 10.0; // PSD above the median to count as vibration peak
+const MAX_PEAKS: usize = 5; // per axis
+
+#[derive(Debug, Clone, Copy)]
+pub struct Peak {
+    pub axis: usize,
+    pub frequency: f64, // Hz
+    pub density: f64,   // unit/√Hz
+}
+
+#[derive(Debug, Clone)]
+pub struct Spectrum {
+    pub frequencies: Vec<f64>, // Hz
+    pub psd: [Vec<f64>; 3],    // unit²/Hz
+    pub density: [f64; 3],     // unit/√Hz, median over all frequencies but DC
+    pub peaks: Vec<Peak>,
+}
+
+/// In-place radix-2 FFT, `re` and `im` must have a power of two length.
+fn fft(re: &mut [f64], im: &mut [f64]) {
+    let n = re.len();
+
+    // bit reversal permutation
+    let mut j = 0;
+    for i in 1..n {
+        let mut bit = n >> 1;
+        while j & bit != 0 {
+            j ^= bit;
+            bit >>= 1;
+        }
+        j |= bit;
+        if i < j {
+            re.swap(i, j);
+            im.swap(i, j);
+        }
+    }
+
+    let mut len = 2;
+    while len <= n {
+        let angle = -2.0 * PI / len as f64;
+        for start in (0..n).step_by(len) {
+            for k in 0..len / 2 {
+                let (sin, cos) = (angle * k as f64).sin_cos();
+                let a = start + k;
+                let b = a + len / 2;
+                let t_re = re[b] * cos - im[b] * sin;
+                let t_im = re[b] * sin + im[b] * cos;
+                re[b] = re[a] - t_re;
+                im[b] = im[a] - t_im;
+                re[a] += t_re;
+                im[a] += t_im;
+            }
+        }
+        len <<= 1;
+    }
+}
+
+/// One-sided PSD of the sample runs taken every `period` seconds, `segment` samples per
+/// FFT (power of two). Segments don't cross runs, None if no run is long enough.
+pub fn welch(runs: &[&[f64]], period: f64, segment: usize) -> Option<Vec<f64>> {
+    let window: Vec<_> = (0..segment)
+        .map(|i| 0.5 - 0.5 * (2.0 * PI * i as f64 / segment as f64).cos())
+        .collect();
+    let window_power: f64 = window.iter().map(|w| w * w).sum();
+
+    let mut psd = vec![0.0; segment / 2 + 1];
+    let mut count = 0;
+    for samples in runs.iter().filter(|r| r.len() >= segment) {
+        for start in (0..=samples.len() - segment).step_by(segment / 2) {
+            let chunk = &samples[start..start + segment];
+            let mean = chunk.iter().sum::<f64>() / segment as f64;
+            let mut re: Vec<_> = chunk
+                .iter()
+                .zip(&window)
+                .map(|(s, w)| (s - mean) * w)
+                .collect();
+            let mut im = vec![0.0; segment];
+            fft(&mut re, &mut im);
+
+            for (k, p) in psd.iter_mut().enumerate() {
+                *p += re[k] * re[k] + im[k] * im[k];
+            }
+            count += 1;
+        }
+    }
+    if count == 0 {
+        return None;
+    }
+
+    for (k, p) in psd.iter_mut().enumerate() {
+        // DC and Nyquist are not mirrored
+        let one_sided = if k == 0 || k == segment / 2 { 1.0 } else { 2.0 };
+        *p *= one_sided * period / (window_power * count as f64);
+    }
+    Some(psd)
+}
+
+fn median(values: &[f64]) -> f64 {
+    let mut sorted = values.to_vec();
+    sorted.sort_by(|a, b| a.total_cmp(b));
+    sorted[sorted.len() / 2]
+}
+
+/// Local maxima standing out of the noise floor, strongest first.
+fn find_peaks(axis: usize, frequencies: &[f64], psd: &[f64]) -> Vec<Peak> {
+    let floor = median(&psd[1..]);
+    let mut peaks: Vec<_> = (2..psd.len() - 1)
+        .filter(|&k| psd[k] > PEAK_FACTOR * floor && psd[k] > psd[k - 1] && psd[k] >= psd[k + 1])
+        .map(|k| Peak {
+            axis,
+            frequency: frequencies[k],
+            density: psd[k].sqrt(),
+        })
+        .collect();
+    peaks.sort_by(|a, b| b.density.total_cmp(&a.density));
+    peaks.truncate(MAX_PEAKS);
+    peaks
+}
+
+/// Spectrum of all three axes over the `still` sample ranges. Needs timestamped samples,
+/// at least one segment within a range.
+pub fn analyze(
+    points: &[Vector3<f64>],
+    info: &[SampleInfo],
+    still: &[Range<usize>],
+    segment: usize,
+) -> Option<Spectrum> {
+    let period = allan::sample_period(info)?;
+
+    let frequencies: Vec<_> = (0..=segment / 2)
+        .map(|k| k as f64 / (segment as f64 * period))
+        .collect();
+    let mut psd = [vec![], vec![], vec![]];
+    for (axis, psd) in psd.iter_mut().enumerate() {
+        let samples: Vec<_> = points.iter().map(|p| p[axis]).collect();
+        let runs: Vec<_> = still
+            .iter()
+            .filter_map(|r| samples.get(r.clone()))
+            .collect();
+        *psd = welch(&runs, period, segment)?;
+    }
+    let density = [0, 1, 2].map(|axis| median(&psd[axis][1..]).sqrt());
+    let peaks = (0..3)
+        .flat_map(|axis| find_peaks(axis, &frequencies, &psd[axis]))
+        .collect();
+
+    Some(Spectrum {
+        frequencies,
+        psd,
+        density,
+        peaks,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn vibration_peak() {
+        // white noise of 0.01 /√Hz at 1 kHz plus a 120 Hz vibration on X
+        let period: f64 = 1e-3;
+        let density = 0.01;
+        let sigma = density / (2.0 * period).sqrt();
+        let mut seed = 7u64;
+        let mut uniform = || {
+            seed = seed
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            ((seed >> 11) as f64 / (1u64 << 53) as f64 - 0.5) * 12f64.sqrt() // unit variance
+        };
+
+        let mut points = vec![];
+        let mut info = vec![];
+        for i in 0..20_000 {
+            let t = i as f64 * period;
+            let vibration = 0.5 * (2.0 * PI * 120.0 * t).sin();
+            points.push(
+                Vector3::new(vibration, 0.0, 0.0)
+                    + Vector3::new(uniform(), uniform(), uniform()) * sigma,
+            );
+            info.push(SampleInfo {
+                device_time: Some(t),
+                ..Default::default()
+            });
+        }
+
+        let all = 0..points.len();
+        let spectrum = analyze(&points, &info, std::slice::from_ref(&all), 512).unwrap();
+        for axis in 0..3 {
+            let d = spectrum.density[axis];
+            assert!((d / density - 1.0).abs() < 0.15, "{d}");
+        }
+        assert!(spectrum.peaks.iter().all(|p| p.axis == 0));
+        assert!((spectrum.peaks[0].frequency - 120.0).abs() < 2.0);
+    }
+}
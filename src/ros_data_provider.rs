@@ -1,14 +1,64 @@
 use std::sync::mpsc::Receiver;
 
 use crate::data_provider::DataProviderUi;
-use crate::data_provider::{ImuData, MagData};
+use crate::data_provider::{ImuData, MagData, SampleInfo};
+use crate::units::{InputUnits, MagUnit};
 use futures::{future, StreamExt};
 use r2r::sensor_msgs;
 use r2r::QosProfile;
 
-pub struct Node {}
+fn sample_info(header: &r2r::std_msgs::msg::Header) -> SampleInfo {
+    SampleInfo {
+        device_time: Some(header.stamp.sec as f64 + header.stamp.nanosec as f64 * 1e-9),
+        ..SampleInfo::received_now()
+    }
+}
+
+/// Topics used when the `imu_topics` / `mag_topics` parameters are not set.
+const DEFAULT_IMU_TOPICS: [&str; 1] = ["/robot/rcu_com/imu"];
+const DEFAULT_MAG_TOPICS: [&str; 1] = ["/robot/rcu_com/mag"];
+
+/// Subscribed topic and the device its samples are tagged with.
+struct Topic {
+    name: String,
+    device: String,
+}
+
+impl Topic {
+    /// Parses a `topic` or `topic=device` parameter entry. Without an explicit device the
+    /// topic namespace is used, so `/imu/data` and `/imu/mag` end up on the same device.
+    /// `header.frame_id` is no good for this, IMU and mag frames usually differ.
+    fn parse(entry: &str) -> Self {
+        let (name, device) = match entry.split_once('=') {
+            Some((name, device)) => (name, device.to_string()),
+            None => match entry.rsplit_once('/') {
+                Some(("", _)) | None => (entry, "/".to_string()),
+                Some((namespace, _)) => (entry, namespace.to_string()),
+            },
+        };
+        Self {
+            name: name.to_string(),
+            device,
+        }
+    }
+}
+
+fn topics(node: &r2r::Node, param: &str, default: &[&str]) -> Vec<Topic> {
+    let entries = match node.params.lock().unwrap().get(param).map(|p| &p.value) {
+        Some(r2r::ParameterValue::StringArray(entries)) => entries.clone(),
+        _ => default.iter().map(|t| t.to_string()).collect(),
+    };
+    entries.iter().map(|e| Topic::parse(e)).collect()
+}
+
+pub struct Node {
+    imu_topics: Vec<Topic>,
+    mag_topics: Vec<Topic>,
+}
 
 impl Node {
+    /// Subscribes to the topics in the `imu_topics` and `mag_topics` string array
+    /// parameters, e.g. `--ros-args -p imu_topics:="['/left/imu', '/right/imu=right']"`.
     pub fn new() -> (Self, r2r::Node, Receiver<ImuData>, Receiver<MagData>) {
         let (imu_tx, imu_rx) = std::sync::mpsc::channel();
         let (mag_tx, mag_rx) = std::sync::mpsc::channel();
@@ -16,63 +66,114 @@ impl Node {
         let ctx = r2r::Context::create().unwrap();
         let mut node = r2r::Node::create(ctx, "imu_cal", "").unwrap();
 
-        let imu_sub = node
-            .subscribe::<sensor_msgs::msg::Imu>("/robot/rcu_com/imu", QosProfile::default())
-            .unwrap();
-        let mag_sub = node
-            .subscribe::<sensor_msgs::msg::MagneticField>(
-                "/robot/rcu_com/mag",
-                QosProfile::default(),
-            )
-            .unwrap();
-
-        tokio::task::spawn(async move {
-            imu_sub
-                .for_each(move |msg| {
-                    imu_tx
-                        .send(ImuData {
-                            lin_acc: nalgebra::Vector3::new(
-                                msg.linear_acceleration.x,
-                                msg.linear_acceleration.y,
-                                msg.linear_acceleration.z,
-                            ),
-                            ang_vel: nalgebra::Vector3::new(
-                                msg.angular_velocity.x,
-                                msg.angular_velocity.y,
-                                msg.angular_velocity.z,
-                            ),
-                        })
-                        .unwrap();
-                    future::ready(())
-                })
-                .await
-        });
-
-        tokio::task::spawn(async move {
-            mag_sub
-                .for_each(move |msg| {
-                    mag_tx
-                        .send(MagData {
-                            field: nalgebra::Vector3::new(
-                                msg.magnetic_field.x,
-                                msg.magnetic_field.y,
-                                msg.magnetic_field.z,
-                            ),
-                        })
-                        .unwrap();
-                    future::ready(())
-                })
-                .await
-        });
-
-        (Self {}, node, imu_rx, mag_rx)
+        let imu_topics = topics(&node, "imu_topics", &DEFAULT_IMU_TOPICS);
+        let mag_topics = topics(&node, "mag_topics", &DEFAULT_MAG_TOPICS);
+
+        for topic in &imu_topics {
+            let imu_sub = node
+                .subscribe::<sensor_msgs::msg::Imu>(&topic.name, QosProfile::default())
+                .unwrap();
+            let imu_tx = imu_tx.clone();
+            let device = topic.device.clone();
+
+            tokio::task::spawn(async move {
+                imu_sub
+                    .for_each(move |msg| {
+                        imu_tx
+                            .send(ImuData {
+                                lin_acc: nalgebra::Vector3::new(
+                                    msg.linear_acceleration.x,
+                                    msg.linear_acceleration.y,
+                                    msg.linear_acceleration.z,
+                                ),
+                                ang_vel: nalgebra::Vector3::new(
+                                    msg.angular_velocity.x,
+                                    msg.angular_velocity.y,
+                                    msg.angular_velocity.z,
+                                ),
+                                info: sample_info(&msg.header),
+                                device: device.clone(),
+                            })
+                            .unwrap();
+                        future::ready(())
+                    })
+                    .await
+            });
+        }
+
+        for topic in &mag_topics {
+            let mag_sub = node
+                .subscribe::<sensor_msgs::msg::MagneticField>(&topic.name, QosProfile::default())
+                .unwrap();
+            let mag_tx = mag_tx.clone();
+            let device = topic.device.clone();
+
+            tokio::task::spawn(async move {
+                mag_sub
+                    .for_each(move |msg| {
+                        mag_tx
+                            .send(MagData {
+                                field: nalgebra::Vector3::new(
+                                    msg.magnetic_field.x,
+                                    msg.magnetic_field.y,
+                                    msg.magnetic_field.z,
+                                ),
+                                info: sample_info(&msg.header),
+                                device: device.clone(),
+                            })
+                            .unwrap();
+                        future::ready(())
+                    })
+                    .await
+            });
+        }
+
+        (
+            Self {
+                imu_topics,
+                mag_topics,
+            },
+            node,
+            imu_rx,
+            mag_rx,
+        )
     }
 }
 
 impl DataProviderUi for Node {
     fn show(&mut self, ui: &mut eframe::egui::Ui) {
         ui.heading("Ros Topics");
-        ui.label("/imu");
-        ui.label("/mag");
+        for topic in self.imu_topics.iter().chain(&self.mag_topics) {
+            ui.label(format!("{} ({})", topic.name, topic.device));
+        }
+    }
+
+    fn input_units(&self) -> Option<InputUnits> {
+        // REP 103, sensor_msgs/MagneticField is in Tesla
+        Some(InputUnits::with_mag(MagUnit::Tesla))
+    }
+
+    fn seq_modulus(&self) -> Option<u64> {
+        // sensor_msgs carry no sequence counter
+        None
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn topic_devices() {
+        let imu = Topic::parse("/robot/rcu_com/imu");
+        let mag = Topic::parse("/robot/rcu_com/mag");
+        assert_eq!(imu.device, "/robot/rcu_com");
+        assert_eq!(imu.device, mag.device);
+
+        assert_eq!(Topic::parse("/imu").device, Topic::parse("/mag").device);
+
+        let mapped = Topic::parse("/sensors/imu_left=left");
+        assert_eq!(mapped.name, "/sensors/imu_left");
+        assert_eq!(mapped.device, "left");
     }
 }
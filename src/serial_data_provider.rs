@@ -1,9 +1,8 @@
 use crate::data_provider::*;
-use bytes::BytesMut;
-use core::str;
+use crate::serial_protocol::{ProtocolCodec, SerialMessage, SerialProtocol};
+use crate::units::InputUnits;
 use eframe::egui;
 use futures::prelude::*;
-use nalgebra::vector;
 use std::sync::mpsc::{Receiver, Sender};
 use stream_cancel::StreamExt;
 use tokio_serial::{SerialPort, SerialPortBuilderExt};
@@ -18,6 +17,7 @@ pub struct SerialDataProvider {
     mag_tx: Sender<MagData>,
     serial_port_info: Option<tokio_serial::SerialPortInfo>,
     baud_rate: u32,
+    protocol: SerialProtocol,
     trigger: Option<stream_cancel::Trigger>,
 }
 
@@ -32,6 +32,7 @@ impl SerialDataProvider {
                 mag_tx,
                 serial_port_info: None,
                 baud_rate: 115200,
+                protocol: SerialProtocol::Text,
                 trigger: None,
             }),
             imu_rx,
@@ -41,6 +42,14 @@ impl SerialDataProvider {
 }
 
 impl DataProviderUi for SerialDataProvider {
+    fn input_units(&self) -> Option<InputUnits> {
+        self.protocol.input_units()
+    }
+
+    fn seq_modulus(&self) -> Option<u64> {
+        self.protocol.seq_modulus()
+    }
+
     fn show(&mut self, ui: &mut eframe::egui::Ui) {
         ui.heading("Serial");
         if self.trigger.is_some() {
@@ -48,6 +57,7 @@ impl DataProviderUi for SerialDataProvider {
                 "'{}' 8-N-1",
                 self.serial_port_info.as_ref().map_or("", |p| &p.port_name)
             ));
+            ui.label(self.protocol.label());
         } else {
             egui::ComboBox::new("ports", "Port")
                 .selected_text(self.serial_port_info.as_ref().map_or("", |p| &p.port_name))
@@ -66,6 +76,14 @@ impl DataProviderUi for SerialDataProvider {
                         );
                     }
                 });
+
+            egui::ComboBox::new("protocols", "Protocol")
+                .selected_text(self.protocol.label())
+                .show_ui(ui, |ui| {
+                    for protocol in SerialProtocol::ALL {
+                        ui.selectable_value(&mut self.protocol, protocol, protocol.label());
+                    }
+                });
         }
 
         egui::ComboBox::new("baudrates", "Baud rate")
@@ -96,7 +114,7 @@ impl DataProviderUi for SerialDataProvider {
                     let (trigger, tripwire) = stream_cancel::Tripwire::new();
                     self.trigger = Some(trigger);
 
-                    let reader = LineCodec.framed(port);
+                    let reader = ProtocolCodec::new(self.protocol).framed(port);
 
                     let imu_tx = self.imu_tx.clone();
                     let mag_tx = self.mag_tx.clone();
@@ -104,46 +122,15 @@ impl DataProviderUi for SerialDataProvider {
                     tokio::spawn(async move {
                         let mut incoming = reader.take_until_if(tripwire);
 
-                        while let Some(line) = incoming.next().await {
-                            if let Ok(line) = line {
-                                let mut gyro_x = 0.0;
-                                let mut gyro_y = 0.0;
-                                let mut gyro_z = 0.0;
-                                let mut acc_x = 0.0;
-                                let mut acc_y = 0.0;
-                                let mut acc_z = 0.0;
-                                let mut mag_x = 0.0;
-                                let mut mag_y = 0.0;
-                                let mut mag_z = 0.0;
-
-                                if scanf::sscanf!(
-                                    &line,
-                                    "imu {} {} {} {} {} {}\n",
-                                    gyro_x,
-                                    gyro_y,
-                                    gyro_z,
-                                    acc_x,
-                                    acc_y,
-                                    acc_z
-                                )
-                                .is_ok()
-                                {
-                                    imu_tx
-                                        .send(ImuData {
-                                            lin_acc: vector![acc_x, acc_y, acc_z],
-                                            ang_vel: vector![gyro_x, gyro_y, gyro_z],
-                                        })
-                                        .ok();
-                                }
-
-                                if scanf::sscanf!(&line, "mag {} {} {}\n", mag_x, mag_y, mag_z,)
-                                    .is_ok()
-                                {
-                                    mag_tx
-                                        .send(MagData {
-                                            field: vector![mag_x, mag_y, mag_z],
-                                        })
-                                        .ok();
+                        while let Some(messages) = incoming.next().await {
+                            for message in messages.into_iter().flatten() {
+                                match message {
+                                    SerialMessage::Imu(imu) => {
+                                        imu_tx.send(imu).ok();
+                                    }
+                                    SerialMessage::Mag(mag) => {
+                                        mag_tx.send(mag).ok();
+                                    }
                                 }
                             }
                         }
@@ -153,25 +140,3 @@ impl DataProviderUi for SerialDataProvider {
         }
     }
 }
-
-struct LineCodec;
-
-impl Decoder for LineCodec {
-    type Item = String;
-    type Error = std::io::Error;
-
-    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
-        let newline = src.as_ref().iter().position(|b| *b == b'\n');
-        if let Some(n) = newline {
-            let line = src.split_to(n + 1);
-            return match str::from_utf8(line.as_ref()) {
-                Ok(s) => Ok(Some(s.to_string())),
-                Err(_) => Err(std::io::Error::new(
-                    std::io::ErrorKind::Other,
-                    "Invalid String",
-                )),
-            };
-        }
-        Ok(None)
-    }
-}
@@ -0,0 +1,648 @@
+use crate::data_provider::*;
+use crate::units::{InputUnits, MagUnit};
+use bytes::{Buf, BytesMut};
+use core::str;
+use nalgebra::{vector, Vector3};
+use tokio_util::codec::Decoder;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SerialProtocol {
+    Text,
+    UbxEsfRaw,
+    XsensMtData2,
+    VectorNav,
+}
+
+impl SerialProtocol {
+    pub const ALL: [SerialProtocol; 4] = [
+        SerialProtocol::Text,
+        SerialProtocol::UbxEsfRaw,
+        SerialProtocol::XsensMtData2,
+        SerialProtocol::VectorNav,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            SerialProtocol::Text => "Text",
+            SerialProtocol::UbxEsfRaw => "u-blox UBX-ESF-RAW",
+            SerialProtocol::XsensMtData2 => "Xsens MTData2",
+            SerialProtocol::VectorNav => "VectorNav ASCII",
+        }
+    }
+
+    /// Units defined by the protocol, the text protocol leaves them to the firmware.
+    pub fn input_units(&self) -> Option<InputUnits> {
+        match self {
+            SerialProtocol::Text => None,
+            SerialProtocol::UbxEsfRaw => Some(InputUnits::default()),
+            SerialProtocol::XsensMtData2 => Some(InputUnits::with_mag(MagUnit::Arbitrary)),
+            SerialProtocol::VectorNav => Some(InputUnits::with_mag(MagUnit::Gauss)),
+        }
+    }
+
+    /// Value the sample counter wraps at, None if it does not wrap or is not sent.
+    pub fn seq_modulus(&self) -> Option<u64> {
+        match self {
+            SerialProtocol::XsensMtData2 => Some(1 << 16), // PacketCounter
+            SerialProtocol::Text | SerialProtocol::UbxEsfRaw | SerialProtocol::VectorNav => None,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum SerialMessage {
+    Imu(ImuData),
+    Mag(MagData),
+}
+
+/// Splits the serial byte stream into frames of the selected protocol and decodes them.
+pub struct ProtocolCodec {
+    protocol: SerialProtocol,
+    esf_pending: EsfRawPending,
+}
+
+impl ProtocolCodec {
+    pub fn new(protocol: SerialProtocol) -> Self {
+        Self {
+            protocol,
+            esf_pending: Default::default(),
+        }
+    }
+}
+
+impl Decoder for ProtocolCodec {
+    type Item = Vec<SerialMessage>;
+    type Error = std::io::Error;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        match self.protocol {
+            SerialProtocol::Text => {
+                let Some(line) = split_line(src)? else {
+                    return Ok(None);
+                };
+                Ok(Some(parse_text_line(&line).into_iter().collect()))
+            }
+            SerialProtocol::VectorNav => {
+                let Some(line) = split_line(src)? else {
+                    return Ok(None);
+                };
+                Ok(Some(parse_vectornav_sentence(&line)))
+            }
+            SerialProtocol::UbxEsfRaw => {
+                let Some((class, id, payload)) = split_ubx_frame(src) else {
+                    return Ok(None);
+                };
+                if (class, id) == UBX_ESF_RAW {
+                    Ok(Some(self.esf_pending.decode(&payload)))
+                } else {
+                    Ok(Some(vec![]))
+                }
+            }
+            SerialProtocol::XsensMtData2 => {
+                let Some((mid, payload)) = split_xbus_frame(src) else {
+                    return Ok(None);
+                };
+                if mid == XBUS_MID_MTDATA2 {
+                    Ok(Some(parse_mtdata2(&payload)))
+                } else {
+                    Ok(Some(vec![]))
+                }
+            }
+        }
+    }
+}
+
+fn split_line(src: &mut BytesMut) -> Result<Option<String>, std::io::Error> {
+    let newline = src.as_ref().iter().position(|b| *b == b'\n');
+    if let Some(n) = newline {
+        let line = src.split_to(n + 1);
+        return match str::from_utf8(line.as_ref()) {
+            Ok(s) => Ok(Some(s.to_string())),
+            Err(_) => Err(std::io::Error::new(
+                std::io::ErrorKind::Other,
+                "Invalid String",
+            )),
+        };
+    }
+    Ok(None)
+}
+
+// Text
+//
+// mag <field_x> <field_y> <field_z> [<time> [<seq> [<temperature>]]]
+// imu <gyro_x> <gyro_y> <gyro_z> <acc_x> <acc_y> <acc_z> [<time> [<seq> [<temperature>]]]
+
+/// Parses the optional trailing fields: device time (s), sequence counter, temperature (°C).
+fn parse_text_info(fields: &[&str]) -> Option<SampleInfo> {
+    let mut info = SampleInfo::received_now();
+    if let Some(time) = fields.first() {
+        info.device_time = Some(time.parse().ok()?);
+    }
+    if let Some(seq) = fields.get(1) {
+        info.seq = Some(seq.parse().ok()?);
+    }
+    if let Some(temperature) = fields.get(2) {
+        info.temperature = Some(temperature.parse().ok()?);
+    }
+    if fields.len() > 3 {
+        return None;
+    }
+    Some(info)
+}
+
+fn parse_text_line(line: &str) -> Option<SerialMessage> {
+    let fields: Vec<_> = line.split_whitespace().collect();
+    let (&kind, fields) = fields.split_first()?;
+    // `imu:<device>` tags the sample with a device id
+    let (kind, device) = kind.split_once(':').unwrap_or((kind, ""));
+    let value_count = match kind {
+        "imu" => 6,
+        "mag" => 3,
+        _ => return None,
+    };
+
+    let values = fields
+        .get(..value_count)?
+        .iter()
+        .map(|f| f.parse::<f64>())
+        .collect::<Result<Vec<_>, _>>()
+        .ok()?;
+    let info = parse_text_info(&fields[value_count..])?;
+
+    Some(match kind {
+        "imu" => SerialMessage::Imu(ImuData {
+            lin_acc: vector![values[3], values[4], values[5]],
+            ang_vel: vector![values[0], values[1], values[2]],
+            info,
+            device: device.to_string(),
+        }),
+        _ => SerialMessage::Mag(MagData {
+            field: vector![values[0], values[1], values[2]],
+            info,
+            device: device.to_string(),
+        }),
+    })
+}
+
+// VectorNav ASCII
+//
+// refs:
+// VectorNav VN-100 User Manual, section "ASCII Output Types"
+//
+// $VNYMR,<yaw>,<pitch>,<roll>,<mag_x>,<mag_y>,<mag_z>,<acc_x>,<acc_y>,<acc_z>,<gyro_x>,<gyro_y>,<gyro_z>*<checksum>
+// $VNIMU,<mag_x>,<mag_y>,<mag_z>,<acc_x>,<acc_y>,<acc_z>,<gyro_x>,<gyro_y>,<gyro_z>,<temp>,<pressure>*<checksum>
+
+fn vectornav_checksum_ok(body: &str, checksum: &str) -> bool {
+    match checksum.len() {
+        2 if checksum == "XX" => true,
+        2 => {
+            let xor = body.bytes().fold(0u8, |acc, b| acc ^ b);
+            u8::from_str_radix(checksum, 16) == Ok(xor)
+        }
+        4 => {
+            let mut crc = 0u16;
+            for b in body.bytes() {
+                crc = crc.rotate_left(8);
+                crc ^= b as u16;
+                crc ^= (crc & 0xff) >> 4;
+                crc ^= crc << 12;
+                crc ^= (crc & 0x00ff) << 5;
+            }
+            u16::from_str_radix(checksum, 16) == Ok(crc)
+        }
+        _ => false,
+    }
+}
+
+fn parse_vectornav_sentence(line: &str) -> Vec<SerialMessage> {
+    let line = line.trim();
+    let Some((body, checksum)) = line.strip_prefix('$').and_then(|l| l.split_once('*')) else {
+        return vec![];
+    };
+    if !vectornav_checksum_ok(body, checksum) {
+        return vec![];
+    }
+
+    let mut fields = body.split(',');
+    let header = fields.next().unwrap_or_default();
+    let Ok(values) = fields
+        .map(|f| f.parse::<f64>())
+        .collect::<Result<Vec<_>, _>>()
+    else {
+        return vec![];
+    };
+
+    let (mag, acc, gyro) = match (header, values.len()) {
+        ("VNYMR", 12) => (3, 6, 9),
+        ("VNIMU", 11) => (0, 3, 6),
+        _ => return vec![],
+    };
+    let v = |i: usize| vector![values[i], values[i + 1], values[i + 2]];
+
+    let mut info = SampleInfo::received_now();
+    if header == "VNIMU" {
+        info.temperature = Some(values[9]);
+    }
+
+    vec![
+        SerialMessage::Imu(ImuData {
+            lin_acc: v(acc),
+            ang_vel: v(gyro),
+            info,
+            device: String::new(),
+        }),
+        SerialMessage::Mag(MagData {
+            field: v(mag),
+            info,
+            device: String::new(),
+        }),
+    ]
+}
+
+// u-blox UBX-ESF-RAW
+//
+// refs:
+// u-blox ZED-F9R Interface Description, section "UBX-ESF-RAW"
+
+const UBX_SYNC: [u8; 2] = [0xB5, 0x62];
+const UBX_ESF_RAW: (u8, u8) = (0x10, 0x03);
+
+/// Returns (class, id, payload) of the next valid frame.
+fn split_ubx_frame(src: &mut BytesMut) -> Option<(u8, u8, Vec<u8>)> {
+    loop {
+        // drop everything in front of the sync chars
+        match src.windows(2).position(|w| w == UBX_SYNC) {
+            Some(n) => src.advance(n),
+            None => {
+                let keep = usize::from(src.last() == Some(&UBX_SYNC[0]));
+                src.advance(src.len() - keep);
+                return None;
+            }
+        }
+
+        if src.len() < 6 {
+            return None;
+        }
+        let len = u16::from_le_bytes([src[4], src[5]]) as usize;
+        if src.len() < 8 + len {
+            src.reserve(8 + len - src.len());
+            return None;
+        }
+
+        // 8-bit Fletcher over class, id, length and payload
+        let (mut ck_a, mut ck_b) = (0u8, 0u8);
+        for b in &src[2..6 + len] {
+            ck_a = ck_a.wrapping_add(*b);
+            ck_b = ck_b.wrapping_add(ck_a);
+        }
+        if [ck_a, ck_b] != src[6 + len..8 + len] {
+            src.advance(1);
+            continue;
+        }
+
+        let frame = src.split_to(8 + len);
+        return Some((frame[2], frame[3], frame[6..6 + len].to_vec()));
+    }
+}
+
+const ESF_RAW_TICK: f64 = 1.0 / 25600.0; // s, resolution of the sensor time tag (~39 µs)
+
+/// ESF-RAW reports one measurement per block, gathers the six axes sharing a sensor time tag.
+#[derive(Debug, Default)]
+struct EsfRawPending {
+    time_tag: u32,
+    gyro: [Option<f64>; 3],
+    acc: [Option<f64>; 3],
+    temperature: Option<f64>,
+}
+
+impl EsfRawPending {
+    fn decode(&mut self, payload: &[u8]) -> Vec<SerialMessage> {
+        let mut messages = vec![];
+
+        // 4 reserved bytes followed by 8 byte blocks
+        for block in payload.get(4..).unwrap_or_default().chunks_exact(8) {
+            let data = u32::from_le_bytes([block[0], block[1], block[2], block[3]]);
+            let time_tag = u32::from_le_bytes([block[4], block[5], block[6], block[7]]);
+            let value = ((data << 8) as i32 >> 8) as f64; // sign extend 24 bits
+            let data_type = (data >> 24) & 0x3f;
+
+            if time_tag != self.time_tag {
+                *self = Self {
+                    time_tag,
+                    temperature: self.temperature,
+                    ..Default::default()
+                };
+            }
+
+            let deg_s = 2f64.powi(-12);
+            let m_s2 = 2f64.powi(-10);
+            match data_type {
+                14 => self.gyro[0] = Some((value * deg_s).to_radians()),
+                13 => self.gyro[1] = Some((value * deg_s).to_radians()),
+                5 => self.gyro[2] = Some((value * deg_s).to_radians()),
+                16 => self.acc[0] = Some(value * m_s2),
+                17 => self.acc[1] = Some(value * m_s2),
+                18 => self.acc[2] = Some(value * m_s2),
+                12 => self.temperature = Some(value * 1e-2),
+                _ => continue,
+            }
+
+            if let ([Some(gx), Some(gy), Some(gz)], [Some(ax), Some(ay), Some(az)]) =
+                (self.gyro, self.acc)
+            {
+                messages.push(SerialMessage::Imu(ImuData {
+                    lin_acc: vector![ax, ay, az],
+                    ang_vel: vector![gx, gy, gz],
+                    info: SampleInfo {
+                        device_time: Some(self.time_tag as f64 * ESF_RAW_TICK),
+                        temperature: self.temperature,
+                        ..SampleInfo::received_now()
+                    },
+                    device: String::new(),
+                }));
+                self.gyro = Default::default();
+                self.acc = Default::default();
+            }
+        }
+
+        messages
+    }
+}
+
+// Xsens MTData2 (XBus)
+//
+// refs:
+// Xsens MT Low Level Communication Protocol Documentation, section "MTData2"
+
+const XBUS_PREAMBLE: u8 = 0xFA;
+const XBUS_BID: u8 = 0xFF;
+const XBUS_MID_MTDATA2: u8 = 0x36;
+
+const XDI_TEMPERATURE: u16 = 0x0810;
+const XDI_PACKET_COUNTER: u16 = 0x1020;
+const XDI_SAMPLE_TIME_FINE: u16 = 0x1060;
+const XDI_ACCELERATION: u16 = 0x4020;
+const XDI_RATE_OF_TURN: u16 = 0x8020;
+const XDI_MAGNETIC_FIELD: u16 = 0xC020;
+
+/// Returns (message id, payload) of the next valid frame.
+fn split_xbus_frame(src: &mut BytesMut) -> Option<(u8, Vec<u8>)> {
+    loop {
+        match src.windows(2).position(|w| w == [XBUS_PREAMBLE, XBUS_BID]) {
+            Some(n) => src.advance(n),
+            None => {
+                let keep = usize::from(src.last() == Some(&XBUS_PREAMBLE));
+                src.advance(src.len() - keep);
+                return None;
+            }
+        }
+
+        if src.len() < 4 {
+            return None;
+        }
+        let (len, header_len) = if src[3] == 0xFF {
+            if src.len() < 6 {
+                return None;
+            }
+            (u16::from_be_bytes([src[4], src[5]]) as usize, 6)
+        } else {
+            (src[3] as usize, 4)
+        };
+        let frame_len = header_len + len + 1;
+        if src.len() < frame_len {
+            src.reserve(frame_len - src.len());
+            return None;
+        }
+
+        // all bytes after the preamble including the checksum add up to zero
+        let sum = src[1..frame_len]
+            .iter()
+            .fold(0u8, |acc, b| acc.wrapping_add(*b));
+        if sum != 0 {
+            src.advance(1);
+            continue;
+        }
+
+        let frame = src.split_to(frame_len);
+        return Some((frame[2], frame[header_len..header_len + len].to_vec()));
+    }
+}
+
+/// Decodes `N` values in the precision given by the lower bits of the data id.
+fn xbus_values<const N: usize>(xdi: u16, data: &[u8]) -> Option<[f64; N]> {
+    let size = match xdi & 0x3 {
+        0 => 4, // float32
+        1 => 4, // fp1220
+        2 => 6, // fp1632
+        _ => 8, // float64
+    };
+    if data.len() < N * size {
+        return None;
+    }
+
+    let component = |b: &[u8]| match xdi & 0x3 {
+        0 => f32::from_be_bytes([b[0], b[1], b[2], b[3]]) as f64,
+        1 => i32::from_be_bytes([b[0], b[1], b[2], b[3]]) as f64 / 2f64.powi(20),
+        2 => {
+            // 32 bit fractional part followed by a 16 bit integer part
+            let frac = u32::from_be_bytes([b[0], b[1], b[2], b[3]]) as i64;
+            let int = i16::from_be_bytes([b[4], b[5]]) as i64;
+            ((int << 32) | frac) as f64 / 2f64.powi(32)
+        }
+        _ => f64::from_be_bytes(b[..8].try_into().unwrap()),
+    };
+
+    Some(std::array::from_fn(|i| {
+        component(&data[i * size..(i + 1) * size])
+    }))
+}
+
+fn xbus_vector(xdi: u16, data: &[u8]) -> Option<Vector3<f64>> {
+    xbus_values::<3>(xdi, data).map(Vector3::from)
+}
+
+fn parse_mtdata2(payload: &[u8]) -> Vec<SerialMessage> {
+    let mut acc = None;
+    let mut gyro = None;
+    let mut mag = None;
+    let mut info = SampleInfo::received_now();
+
+    let mut pos = 0;
+    while pos + 3 <= payload.len() {
+        let xdi = u16::from_be_bytes([payload[pos], payload[pos + 1]]);
+        let size = payload[pos + 2] as usize;
+        let Some(data) = payload.get(pos + 3..pos + 3 + size) else {
+            break;
+        };
+        pos += 3 + size;
+
+        // ignore the coordinate system bits, only ENU/NED/NWU sensor frame data is requested
+        match xdi & 0xFFF0 {
+            XDI_TEMPERATURE => info.temperature = xbus_values::<1>(xdi, data).map(|t| t[0]),
+            XDI_PACKET_COUNTER if size == 2 => {
+                info.seq = Some(u16::from_be_bytes([data[0], data[1]]) as u32)
+            }
+            XDI_SAMPLE_TIME_FINE if size == 4 => {
+                // 10 kHz ticks
+                let ticks = u32::from_be_bytes([data[0], data[1], data[2], data[3]]);
+                info.device_time = Some(ticks as f64 * 1e-4);
+            }
+            XDI_ACCELERATION => acc = xbus_vector(xdi, data),
+            XDI_RATE_OF_TURN => gyro = xbus_vector(xdi, data),
+            XDI_MAGNETIC_FIELD => mag = xbus_vector(xdi, data),
+            _ => (),
+        }
+    }
+
+    let mut messages = vec![];
+    if let (Some(lin_acc), Some(ang_vel)) = (acc, gyro) {
+        messages.push(SerialMessage::Imu(ImuData {
+            lin_acc,
+            ang_vel,
+            info,
+            device: String::new(),
+        }));
+    }
+    if let Some(field) = mag {
+        messages.push(SerialMessage::Mag(MagData {
+            field,
+            info,
+            device: String::new(),
+        }));
+    }
+    messages
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn decode_all(protocol: SerialProtocol, bytes: &[u8]) -> Vec<SerialMessage> {
+        let mut codec = ProtocolCodec::new(protocol);
+        let mut src = BytesMut::from(bytes);
+        let mut messages = vec![];
+        while let Some(m) = codec.decode(&mut src).unwrap() {
+            messages.extend(m);
+        }
+        messages
+    }
+
+    #[test]
+    fn text_optional_fields() {
+        let messages = decode_all(
+            SerialProtocol::Text,
+            b"imu 0.1 0.2 0.3 0.0 0.0 9.81\nmag:left 1 2 3 12.5 42 21.5\nmag 1 2\n",
+        );
+        assert_eq!(messages.len(), 2);
+        match &messages[1] {
+            SerialMessage::Mag(m) => {
+                assert_eq!(m.info.device_time, Some(12.5));
+                assert_eq!(m.info.seq, Some(42));
+                assert_eq!(m.info.temperature, Some(21.5));
+                assert_eq!(m.device, "left");
+            }
+            _ => panic!("expected mag"),
+        }
+    }
+
+    #[test]
+    fn vectornav_ymr() {
+        let body = "VNYMR,+010.071,-000.278,-000.217,+0.2193,+0.0146,+0.4681,-00.023,+00.018,-09.781,+0.001,-0.002,+0.000";
+        let xor = body.bytes().fold(0u8, |acc, b| acc ^ b);
+        let sentence = format!("${}*{:02X}\r\n", body, xor);
+
+        let messages = decode_all(SerialProtocol::VectorNav, sentence.as_bytes());
+        assert_eq!(messages.len(), 2);
+        match &messages[1] {
+            SerialMessage::Mag(m) => {
+                assert!((m.field - vector![0.2193, 0.0146, 0.4681]).norm() < 1e-9)
+            }
+            _ => panic!("expected mag"),
+        }
+
+        // corrupted checksum
+        let sentence = format!("${}*{:02X}\r\n", body, xor ^ 1);
+        assert!(decode_all(SerialProtocol::VectorNav, sentence.as_bytes()).is_empty());
+    }
+
+    #[test]
+    fn ubx_esf_raw() {
+        let mut payload = vec![0u8; 4];
+        for (data_type, value) in [
+            (14u32, 4096i32),
+            (13, 0),
+            (5, -4096),
+            (16, 1024),
+            (17, 0),
+            (18, -10042),
+        ] {
+            let data = ((value as u32) & 0x00ff_ffff) | (data_type << 24);
+            payload.extend_from_slice(&data.to_le_bytes());
+            payload.extend_from_slice(&1234u32.to_le_bytes());
+        }
+
+        let mut frame = UBX_SYNC.to_vec();
+        frame.extend_from_slice(&[UBX_ESF_RAW.0, UBX_ESF_RAW.1]);
+        frame.extend_from_slice(&(payload.len() as u16).to_le_bytes());
+        frame.extend_from_slice(&payload);
+        let (mut ck_a, mut ck_b) = (0u8, 0u8);
+        for b in &frame[2..] {
+            ck_a = ck_a.wrapping_add(*b);
+            ck_b = ck_b.wrapping_add(ck_a);
+        }
+        frame.extend_from_slice(&[ck_a, ck_b]);
+
+        // leading garbage is skipped
+        let mut bytes = vec![0x00, 0xB5, 0x13];
+        bytes.extend(frame);
+
+        let messages = decode_all(SerialProtocol::UbxEsfRaw, &bytes);
+        assert_eq!(messages.len(), 1);
+        match &messages[0] {
+            SerialMessage::Imu(imu) => {
+                assert!(
+                    (imu.ang_vel - vector![1f64.to_radians(), 0.0, -1f64.to_radians()]).norm()
+                        < 1e-12
+                );
+                assert!((imu.lin_acc - vector![1.0, 0.0, -10042.0 / 1024.0]).norm() < 1e-12);
+                assert!((imu.info.device_time.unwrap() - 1234.0 / 25600.0).abs() < 1e-12);
+            }
+            _ => panic!("expected imu"),
+        }
+    }
+
+    #[test]
+    fn xsens_mtdata2() {
+        let mut payload = vec![];
+        for (xdi, v) in [
+            (XDI_ACCELERATION, [0.1f32, 0.2, 9.8]),
+            (XDI_RATE_OF_TURN, [0.01, -0.02, 0.03]),
+        ] {
+            payload.extend_from_slice(&xdi.to_be_bytes());
+            payload.push(12);
+            for c in v {
+                payload.extend_from_slice(&c.to_be_bytes());
+            }
+        }
+
+        let mut frame = vec![
+            XBUS_PREAMBLE,
+            XBUS_BID,
+            XBUS_MID_MTDATA2,
+            payload.len() as u8,
+        ];
+        frame.extend_from_slice(&payload);
+        let sum = frame[1..].iter().fold(0u8, |acc, b| acc.wrapping_add(*b));
+        frame.push(0u8.wrapping_sub(sum));
+
+        let messages = decode_all(SerialProtocol::XsensMtData2, &frame);
+        assert_eq!(messages.len(), 1);
+        match &messages[0] {
+            SerialMessage::Imu(imu) => {
+                assert!((imu.lin_acc - vector![0.1, 0.2, 9.8]).norm() < 1e-6)
+            }
+            _ => panic!("expected imu"),
+        }
+    }
+}
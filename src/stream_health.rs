@@ -0,0 +1,229 @@
+use crate::data_provider::SampleInfo;
+use std::collections::VecDeque;
+
+const INTERVAL_HISTORY: usize = 2000;
+const REORDER_WINDOW: u64 = 64; // samples, a counter going back further was reset
+
+/// Tracks dropped, duplicated and out-of-order samples as well as the timing jitter of a
+/// stream. Sequence numbers are preferred, device timestamps are used otherwise. The host
+/// receive time is only used for the jitter as it is subject to OS and USB buffering.
+#[derive(Debug, Default)]
+pub struct StreamHealth {
+    last_seq: Option<u32>,
+    last_device_time: Option<f64>,
+    last_host_time: Option<f64>,
+    intervals: VecDeque<f64>, // s
+    period: Option<f64>,      // s, median of the intervals, updated every few samples
+    pub received: usize,
+    pub dropped: usize,
+    pub duplicates: usize,
+    pub out_of_order: usize,
+    pub resets: usize, // sequence counter restarted, e.g. by a device reboot
+}
+
+impl StreamHealth {
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+
+    /// `seq_modulus` is the value the sequence counter wraps at, a property of the protocol.
+    /// None if the counter does not wrap.
+    pub fn update(&mut self, info: &SampleInfo, seq_modulus: Option<u64>) {
+        self.received += 1;
+
+        if let Some(seq) = info.seq {
+            if let Some(last) = self.last_seq {
+                let (seq, last) = (seq as u64, last as u64);
+                // steps the counter went forward and backward
+                let (ahead, behind) = match seq_modulus {
+                    Some(m) => ((seq + m - last % m) % m, (last + m - seq % m) % m),
+                    None => (seq.saturating_sub(last), last.saturating_sub(seq)),
+                };
+                // a gap large compared to the counter range is no run of dropped samples
+                let max_gap = seq_modulus.map_or(u64::MAX, |m| m / 4);
+
+                if ahead == 0 && behind == 0 {
+                    self.duplicates += 1;
+                    return;
+                } else if ahead > 0 && ahead <= max_gap {
+                    self.dropped += (ahead - 1) as usize;
+                } else if behind > 0 && behind <= REORDER_WINDOW.min(max_gap) {
+                    self.out_of_order += 1;
+                    return;
+                } else {
+                    self.resets += 1;
+                }
+            }
+            self.last_seq = Some(seq);
+        }
+
+        if let Some(time) = info.device_time {
+            if let Some(last) = self.last_device_time {
+                let dt = time - last;
+                if dt == 0.0 {
+                    if info.seq.is_none() {
+                        self.duplicates += 1;
+                    }
+                    return;
+                } else if dt < 0.0 {
+                    if info.seq.is_none() {
+                        self.out_of_order += 1;
+                    }
+                    return;
+                }
+
+                if info.seq.is_none() {
+                    if let Some(period) = self.nominal_period() {
+                        if dt > 1.5 * period {
+                            self.dropped += ((dt / period).round() as usize).saturating_sub(1);
+                        }
+                    }
+                }
+                self.push_interval(dt);
+            }
+            self.last_device_time = Some(time);
+        } else if let Some(time) = info.host_time {
+            if let Some(last) = self.last_host_time {
+                if time > last {
+                    self.push_interval(time - last);
+                }
+            }
+            self.last_host_time = Some(time);
+        }
+    }
+
+    fn push_interval(&mut self, dt: f64) {
+        if self.intervals.len() == INTERVAL_HISTORY {
+            self.intervals.pop_front();
+        }
+        self.intervals.push_back(dt);
+
+        if self.intervals.len() >= 10
+            && (self.period.is_none() || self.received.is_multiple_of(100))
+        {
+            let mut sorted: Vec<_> = self.intervals.iter().copied().collect();
+            sorted.sort_by(|a, b| a.total_cmp(b));
+            self.period = Some(sorted[sorted.len() / 2]);
+        }
+    }
+
+    /// Median sample interval in s.
+    pub fn nominal_period(&self) -> Option<f64> {
+        self.period
+    }
+
+    /// Deviation of each interval from the nominal period in s. Gaps caused by dropped
+    /// samples are excluded.
+    pub fn jitter_samples(&self) -> Vec<f64> {
+        let Some(period) = self.nominal_period() else {
+            return vec![];
+        };
+        self.intervals
+            .iter()
+            .filter(|dt| **dt < 1.5 * period)
+            .map(|dt| dt - period)
+            .collect()
+    }
+
+    /// RMS jitter in s.
+    pub fn jitter(&self) -> Option<f64> {
+        let samples = self.jitter_samples();
+        if samples.is_empty() {
+            return None;
+        }
+        Some((samples.iter().map(|j| j * j).sum::<f64>() / samples.len() as f64).sqrt())
+    }
+
+    /// Dropped samples in % of all expected samples.
+    pub fn drop_rate(&self) -> f64 {
+        let expected = self.received + self.dropped;
+        if expected == 0 {
+            return 0.0;
+        }
+        self.dropped as f64 / expected as f64 * 100.0
+    }
+
+    pub fn summary(&self) -> String {
+        if self.received == 0 {
+            return "-".to_string();
+        }
+        let jitter = match self.jitter() {
+            Some(jitter) => format!("{:.2} ms", jitter * 1e3),
+            None => "-".to_string(),
+        };
+        format!(
+            "drop {:.1}%  dup {}  ooo {}  reset {}  jitter {}",
+            self.drop_rate(),
+            self.duplicates,
+            self.out_of_order,
+            self.resets,
+            jitter
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn info(seq: Option<u32>, device_time: Option<f64>) -> SampleInfo {
+        SampleInfo {
+            seq,
+            device_time,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn sequence_numbers() {
+        let mut health = StreamHealth::default();
+        for seq in [65533, 65534, 65535, 0, 3, 3, 2, 4] {
+            health.update(&info(Some(seq), None), Some(1 << 16));
+        }
+        assert_eq!(health.dropped, 2); // 1, 2 after wrapping
+        assert_eq!(health.duplicates, 1);
+        assert_eq!(health.out_of_order, 1);
+        assert_eq!(health.resets, 0);
+    }
+
+    #[test]
+    fn sequence_reorder_and_reset() {
+        // a 16 bit counter going back is no wrap with thousands of drops
+        let mut health = StreamHealth::default();
+        for seq in [998, 1000, 999, 1001, 5, 6, 7] {
+            health.update(&info(Some(seq), None), Some(1 << 16));
+        }
+        assert_eq!(health.dropped, 1); // 999, before it came late
+        assert_eq!(health.out_of_order, 1);
+        assert_eq!(health.resets, 1);
+
+        // a counter wrapping at another value than a power of two
+        let mut health = StreamHealth::default();
+        for seq in [998, 999, 0, 2] {
+            health.update(&info(Some(seq), None), Some(1000));
+        }
+        assert_eq!(health.dropped, 1);
+        assert_eq!(health.resets, 0);
+
+        // without a known width going back is a reset, not a wrap
+        let mut health = StreamHealth::default();
+        for seq in [65534, 65535, 0, 1] {
+            health.update(&info(Some(seq), None), None);
+        }
+        assert_eq!(health.dropped, 0);
+        assert_eq!(health.resets, 1);
+    }
+
+    #[test]
+    fn device_time() {
+        let mut health = StreamHealth::default();
+        for i in 0..100 {
+            if i % 10 != 5 {
+                health.update(&info(None, Some(i as f64 * 0.01)), None);
+            }
+        }
+        assert_eq!(health.dropped, 9); // the first gap happens before the period is known
+        assert!((health.nominal_period().unwrap() - 0.01).abs() < 1e-9);
+        assert!(health.jitter().unwrap() < 1e-9);
+    }
+}
@@ -0,0 +1,267 @@
+// Temperature compensation
+//
+// The drift is fitted to still periods only, each one held in a single orientation. The
+// mean of every period is removed, so the orientations and the motion in between don't
+// end up in the curves, only the change with the temperature within a period does. A
+// dedicated sweep is one long still period. A curve describes the drift relative to the
+// reference temperature, so it has no constant term and goes on top of the regular
+// calibration: the drift is removed from the raw value first.
+
+use crate::data_provider::SampleInfo;
+use nalgebra::{DMatrix, DVector, Vector3};
+
+const BIN_WIDTH: f64 = 1.0; // °C
+const MIN_BIN_SAMPLES: usize = 10;
+const MIN_POSE_SAMPLES: usize = 10;
+pub const MIN_SPAN: f64 = 2.0; // °C within a still period, a narrower sweep says nothing about the drift
+
+/// Samples (°C, value) of one still period.
+pub type PoseSamples = Vec<(f64, Vector3<f64>)>;
+
+/// Mean deviation from the still period means of the samples within one temperature bin.
+#[derive(Debug, Clone, Copy)]
+pub struct TempBin {
+    pub temperature: f64,   // °C, mean
+    pub mean: Vector3<f64>, // mean deviation
+}
+
+#[derive(Debug, Clone, Default, serde::Serialize)]
+pub struct TempCurve {
+    // bias drift: sum of c_k * (T - t_ref)^k, k = 1..
+    pub bias: Vec<Vector3<f64>>,
+    // relative scale drift, same form, empty if not fitted
+    pub scale: Vec<f64>,
+}
+
+impl TempCurve {
+    pub fn bias_drift(&self, t: f64, t_ref: f64) -> Vector3<f64> {
+        let dt = t - t_ref;
+        self.bias
+            .iter()
+            .enumerate()
+            .map(|(k, c)| c * dt.powi(k as i32 + 1))
+            .sum()
+    }
+
+    pub fn scale_drift(&self, t: f64, t_ref: f64) -> f64 {
+        let dt = t - t_ref;
+        1.0 + self
+            .scale
+            .iter()
+            .enumerate()
+            .map(|(k, c)| c * dt.powi(k as i32 + 1))
+            .sum::<f64>()
+    }
+
+    /// Brings a raw value measured at `t` to what it would read at `t_ref`.
+    pub fn compensate(&self, v: &Vector3<f64>, t: f64, t_ref: f64) -> Vector3<f64> {
+        (v - self.bias_drift(t, t_ref)) / self.scale_drift(t, t_ref)
+    }
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct TempCal {
+    pub t_ref: f64, // °C
+    pub degree: usize,
+    pub gyro: Option<TempCurve>,
+    pub acc: Option<TempCurve>,
+    pub mag: Option<TempCurve>,
+}
+
+/// Groups the samples with a temperature by still period, `pose` is the period of each
+/// sample, None while moving.
+pub fn group_by_pose(
+    points: &[Vector3<f64>],
+    temperatures: &[Option<f64>],
+    pose: &[Option<usize>],
+) -> Vec<PoseSamples> {
+    let mut poses: std::collections::BTreeMap<usize, PoseSamples> = Default::default();
+    for ((p, t), pose) in points.iter().zip(temperatures).zip(pose) {
+        if let (Some(t), Some(pose)) = (t, pose) {
+            poses.entry(*pose).or_default().push((*t, *p));
+        }
+    }
+    poses
+        .into_values()
+        .filter(|p| p.len() >= MIN_POSE_SAMPLES)
+        .collect()
+}
+
+fn pose_mean(samples: &[(f64, Vector3<f64>)]) -> Vector3<f64> {
+    samples.iter().map(|(_, p)| p).sum::<Vector3<f64>>() / samples.len() as f64
+}
+
+pub fn bin(poses: &[PoseSamples]) -> Vec<TempBin> {
+    let mut bins: std::collections::BTreeMap<i64, (f64, Vector3<f64>, usize)> = Default::default();
+    for samples in poses {
+        let mean = pose_mean(samples);
+        for (t, p) in samples {
+            let bin =
+                bins.entry((t / BIN_WIDTH).floor() as i64)
+                    .or_insert((0.0, Vector3::zeros(), 0));
+            bin.0 += t;
+            bin.1 += p - mean;
+            bin.2 += 1;
+        }
+    }
+
+    bins.values()
+        .filter(|(_, _, n)| *n >= MIN_BIN_SAMPLES)
+        .map(|(t, sum, n)| TempBin {
+            temperature: t / *n as f64,
+            mean: sum / *n as f64,
+        })
+        .collect()
+}
+
+/// Least squares drift polynomial without constant term, y = offset of the period + sum of
+/// c_k * x^k, k = 1..=degree. The offsets are eliminated by removing the period means
+/// from x^k and y. Returns c_1..c_degree.
+fn fit_within(poses: &[Vec<(f64, f64)>], degree: usize) -> Option<Vec<f64>> {
+    let mut ata = DMatrix::zeros(degree, degree);
+    let mut aty = DVector::zeros(degree);
+    for samples in poses {
+        let n = samples.len() as f64;
+        let x_mean: Vec<f64> = (1..=degree)
+            .map(|k| samples.iter().map(|(x, _)| x.powi(k as i32)).sum::<f64>() / n)
+            .collect();
+        let y_mean = samples.iter().map(|(_, y)| y).sum::<f64>() / n;
+        for (x, y) in samples {
+            let row = DVector::from_fn(degree, |k, _| x.powi(k as i32 + 1) - x_mean[k]);
+            ata += &row * row.transpose();
+            aty += &row * (y - y_mean);
+        }
+    }
+    let c = ata.svd(true, true).solve(&aty, 1e-12).ok()?;
+    Some(c.iter().copied().collect())
+}
+
+/// Fits the drift curves to the still periods. With `fit_scale` the magnitude change is
+/// taken as scale drift first, relative to the magnitude of each period.
+pub fn fit_curve(
+    poses: &[PoseSamples],
+    t_ref: f64,
+    degree: usize,
+    fit_scale: bool,
+) -> Option<TempCurve> {
+    let span = poses
+        .iter()
+        .map(|samples| {
+            let (min, max) = samples
+                .iter()
+                .fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), (t, _)| {
+                    (min.min(*t), max.max(*t))
+                });
+            max - min
+        })
+        .fold(0.0, f64::max);
+    if degree == 0 || span < MIN_SPAN {
+        return None;
+    }
+
+    let mut curve = TempCurve::default();
+    if fit_scale {
+        let relative: Vec<Vec<_>> = poses
+            .iter()
+            .map(|samples| {
+                let norm =
+                    samples.iter().map(|(_, p)| p.norm()).sum::<f64>() / samples.len() as f64;
+                samples
+                    .iter()
+                    .map(|(t, p)| (t - t_ref, p.norm() / norm))
+                    .collect()
+            })
+            .collect();
+        curve.scale = fit_within(&relative, degree)?;
+    }
+
+    let mut axes = vec![];
+    for axis in 0..3 {
+        let values: Vec<Vec<_>> = poses
+            .iter()
+            .map(|samples| {
+                samples
+                    .iter()
+                    .map(|(t, p)| (t - t_ref, p[axis] / curve.scale_drift(*t, t_ref)))
+                    .collect()
+            })
+            .collect();
+        axes.push(fit_within(&values, degree)?);
+    }
+    curve.bias = (0..degree)
+        .map(|k| Vector3::new(axes[0][k], axes[1][k], axes[2][k]))
+        .collect();
+
+    Some(curve)
+}
+
+/// Mean temperature of all samples, the reference of the curves.
+pub fn mean_temperature(info: &[&[SampleInfo]]) -> Option<f64> {
+    let temps: Vec<_> = info
+        .iter()
+        .flat_map(|i| i.iter().filter_map(|i| i.temperature))
+        .collect();
+    (!temps.is_empty()).then(|| temps.iter().sum::<f64>() / temps.len() as f64)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn drift(t: f64) -> Vector3<f64> {
+        Vector3::new(1e-3, -2e-3, 5e-4) * (t - 20.0)
+            + Vector3::new(2e-5, 0.0, -1e-5) * (t - 20.0).powi(2)
+    }
+
+    #[test]
+    fn gyro_bias_drift() {
+        // single still period over the sweep
+        let sweep: PoseSamples = (0..4000)
+            .map(|i| {
+                let t = -10.0 + i as f64 * 0.01; // -10 .. 30 °C
+                (t, Vector3::new(0.01, 0.02, -0.01) + drift(t))
+            })
+            .collect();
+
+        let curve = fit_curve(std::slice::from_ref(&sweep), 20.0, 2, false).unwrap();
+        let v = curve.compensate(&sweep[0].1, -10.0, 20.0);
+        assert!((v - Vector3::new(0.01, 0.02, -0.01)).norm() < 1e-4);
+    }
+
+    #[test]
+    fn poses_without_drift() {
+        // warming up while turned through six orientations, the values only change with
+        // the orientation
+        let faces = [
+            Vector3::new(0.0, 0.0, 9.81),
+            Vector3::new(0.0, 0.0, -9.81),
+            Vector3::new(9.81, 0.0, 0.0),
+            Vector3::new(-9.81, 0.0, 0.0),
+            Vector3::new(0.0, 9.81, 0.0),
+            Vector3::new(0.0, -9.81, 0.0),
+        ];
+        let poses: Vec<PoseSamples> = faces
+            .iter()
+            .enumerate()
+            .map(|(i, g)| {
+                (0..500)
+                    .map(|j| (20.0 + i as f64 * 2.0 + j as f64 * 0.005, *g))
+                    .collect()
+            })
+            .collect();
+
+        let curve = fit_curve(&poses, 25.0, 2, true).unwrap();
+        for t in [20.0, 25.0, 32.0] {
+            assert!(curve.bias_drift(t, 25.0).norm() < 1e-9);
+            assert!((curve.scale_drift(t, 25.0) - 1.0).abs() < 1e-9);
+        }
+
+        // the same with a real drift on top
+        let drifting: Vec<PoseSamples> = poses
+            .iter()
+            .map(|p| p.iter().map(|(t, v)| (*t, v + drift(*t))).collect())
+            .collect();
+        let curve = fit_curve(&drifting, 20.0, 2, false).unwrap();
+        assert!((curve.bias_drift(30.0, 20.0) - drift(30.0)).norm() < 1e-9);
+    }
+}
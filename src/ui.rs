@@ -1,7 +1,20 @@
 use std::sync::mpsc::Receiver;
 
+use crate::alignment::{align, Alignment};
+use crate::allan::{self, AllanAnalysis};
+use crate::axis_map::{Axis, AxisMap};
 use crate::cal::*;
 use crate::data_provider::*;
+use crate::gyro_cal::GyroModel;
+use crate::log_import::{LogImport, SensorKind};
+use crate::poses::{self, Pose};
+use crate::profiles::{SensorProfile, PROFILES};
+use crate::psd::{self, Spectrum};
+use crate::stream_health::StreamHealth;
+use crate::temp_comp::{self, TempBin};
+use crate::units::{AccUnit, GyroUnit, InputUnits, MagUnit};
+use crate::warmup;
+use crate::wizard::{SixFaceWizard, WizardState, FACES};
 use eframe::egui::{self, Color32, RichText};
 use eframe::egui::{Style, Visuals};
 use egui::menu;
@@ -10,6 +23,9 @@ use egui_plot::Legend;
 use std::time::{Duration, Instant};
 
 const VERSION: &str = env!("CARGO_PKG_VERSION");
+const WIZARD_DURATION: f64 = 5.0; // s per face
+const WARMUP_WINDOW: f64 = 2.0; // s averaged per bias estimate
+const PSD_SEGMENTS: [usize; 7] = [64, 128, 256, 512, 1024, 2048, 4096];
 
 #[derive(Debug)]
 struct MessageRate {
@@ -66,6 +82,7 @@ impl MessageRate {
 enum PlotType {
     Scatter,
     Histogram(usize),
+    Psd(usize, Option<Spectrum>), // segment length, computed on request
 }
 
 impl PartialEq for PlotType {
@@ -105,7 +122,8 @@ struct MyApp {
     data_provider: Box<dyn DataProviderUi>,
     imu_rx: Receiver<ImuData>,
     mag_rx: Receiver<MagData>,
-    cal: Cal,
+    devices: Vec<Device>,
+    selected_device: usize,
     collect_mag: bool,
     collect_gyro: bool,
     collect_acc: bool,
@@ -113,18 +131,9 @@ struct MyApp {
     show_gyro: bool,
     show_acc: bool,
     filter_standstill: bool,
-    cal_data: Option<CalData>,
-
-    gyro_plot_type: PlotType,
-    acc_plot_type: PlotType,
-    mag_plot_type: PlotType,
-    gyro_cal_plot_type: PlotType,
-    acc_cal_plot_type: PlotType,
-    mag_cal_plot_type: PlotType,
-
-    gyro_rate: MessageRate,
-    acc_rate: MessageRate,
-    mag_rate: MessageRate,
+    log_import: Option<LogImport>,
+    align_reference: usize,
+    alignment: Option<(String, String, Alignment)>, // device labels and their alignment
 }
 
 impl MyApp {
@@ -137,7 +146,8 @@ impl MyApp {
             data_provider,
             imu_rx,
             mag_rx,
-            cal: Cal::new(),
+            devices: vec![Device::new(String::new())],
+            selected_device: 0,
             collect_mag: false,
             collect_gyro: true,
             collect_acc: false,
@@ -145,7 +155,88 @@ impl MyApp {
             show_acc: true,
             show_mag: true,
             filter_standstill: false,
+            log_import: None,
+            align_reference: 0,
+            alignment: None,
+        }
+    }
+
+    /// Index of the device with the given id, a new device is added on first sight.
+    fn device_index(&mut self, id: &str) -> usize {
+        match self.devices.iter().position(|d| d.id == id) {
+            Some(i) => i,
+            None => {
+                self.devices.push(Device::new(id.to_string()));
+                self.devices.len() - 1
+            }
+        }
+    }
+
+    fn all_cal_data_json(&self) -> String {
+        let cal_data: std::collections::BTreeMap<_, _> = self
+            .devices
+            .iter()
+            .filter_map(|d| Some((d.label(), d.cal_data.as_ref()?)))
+            .collect();
+        serde_json::to_string_pretty(&cal_data).unwrap()
+    }
+}
+
+/// Gyro samples of a warm-up recording, kept apart from the calibration samples.
+#[derive(Default)]
+struct WarmupRecording {
+    active: bool,
+    points: Vec<nalgebra::Vector3<f64>>,
+    info: Vec<SampleInfo>,
+}
+
+/// One IMU of the session, samples are routed by the device id set by the provider.
+struct Device {
+    id: String,
+    cal: Cal,
+    cal_data: Option<CalData>,
+    input_units: InputUnits, // used if the provider does not declare its units
+    profile: Option<SensorProfile>,
+
+    gyro_plot_type: PlotType,
+    acc_plot_type: PlotType,
+    mag_plot_type: PlotType,
+    gyro_cal_plot_type: PlotType,
+    acc_cal_plot_type: PlotType,
+    mag_cal_plot_type: PlotType,
+
+    gyro_rate: MessageRate,
+    acc_rate: MessageRate,
+    mag_rate: MessageRate,
+
+    gyro_health: StreamHealth,
+    acc_health: StreamHealth,
+    mag_health: StreamHealth,
+    show_gyro_jitter: bool,
+    show_acc_jitter: bool,
+    show_mag_jitter: bool,
+    wizard: Option<SixFaceWizard>,
+    acc_poses: (usize, Vec<Pose>), // accel sample count they were found in
+    show_temperature: bool,
+    temperature_sensor: SensorKind,
+    temperature_bins: Option<Vec<TempBin>>,
+    show_allan: bool,
+    allan_sensor: SensorKind,
+    allan: Option<AllanAnalysis>,
+    show_warmup: bool,
+    warmup_tolerance: f64, // rad/s
+    warmup: WarmupRecording,
+    gyrocompass: Option<(f64, Option<f64>)>,
+}
+
+impl Device {
+    fn new(id: String) -> Self {
+        Self {
+            id,
+            cal: Cal::new(),
             cal_data: None,
+            input_units: Default::default(),
+            profile: None,
             gyro_plot_type: PlotType::Scatter,
             acc_plot_type: PlotType::Scatter,
             mag_plot_type: PlotType::Scatter,
@@ -155,42 +246,129 @@ impl MyApp {
             gyro_rate: Default::default(),
             acc_rate: Default::default(),
             mag_rate: Default::default(),
+            gyro_health: Default::default(),
+            acc_health: Default::default(),
+            mag_health: Default::default(),
+            show_gyro_jitter: false,
+            show_acc_jitter: false,
+            show_mag_jitter: false,
+            wizard: None,
+            acc_poses: (0, vec![]),
+            show_temperature: false,
+            temperature_sensor: SensorKind::Gyro,
+            temperature_bins: None,
+            show_allan: false,
+            allan_sensor: SensorKind::Gyro,
+            allan: None,
+            show_warmup: false,
+            warmup_tolerance: 0.01f64.to_radians(),
+            warmup: Default::default(),
+            gyrocompass: None,
+        }
+    }
+
+    fn label(&self) -> &str {
+        if self.id.is_empty() {
+            "default"
+        } else {
+            &self.id
+        }
+    }
+
+    /// Scales (unless the provider declares its units), mag axes and still thresholds of
+    /// the chip.
+    fn apply_profile(&mut self, profile: &SensorProfile) {
+        self.input_units = profile.units;
+        if self.cal.axis_maps.mag != profile.mag_axis_map {
+            self.cal.axis_maps.mag = profile.mag_axis_map;
+            self.cal.clear_mag_measurements();
         }
+        self.cal.acc_still_threshold = profile.acc_still_threshold();
+        self.cal.gyro_still_threshold = profile.gyro_still_threshold();
     }
 }
 
 impl eframe::App for MyApp {
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        let provider_units = self.data_provider.input_units();
+        let seq_modulus = self.data_provider.seq_modulus();
+
         while let Ok(msg) = self.imu_rx.try_recv() {
-            if self.collect_acc {
-                self.acc_rate.received();
+            let i = self.device_index(&msg.device);
+            let device = &mut self.devices[i];
+            let units = provider_units.unwrap_or(device.input_units);
+            let mut msg = units.normalize_imu(msg);
+            msg.lin_acc = device.cal.axis_maps.acc.apply(msg.lin_acc);
+            msg.ang_vel = device.cal.axis_maps.gyro.apply(msg.ang_vel);
+
+            if let Some(wizard) = device.wizard.as_mut() {
+                device.acc_rate.received();
+                device.acc_health.update(&msg.info, seq_modulus);
+                let time = msg.info.host_time.or(msg.info.device_time);
+                if wizard.update(msg.lin_acc, time) {
+                    device.cal.add_acc_measurement(msg.lin_acc, msg.info);
+                }
+            } else if self.collect_acc {
+                device.acc_rate.received();
+                device.acc_health.update(&msg.info, seq_modulus);
                 if self.filter_standstill {
-                    self.cal.add_acc_measurement_still(msg.lin_acc);
+                    device.cal.add_acc_measurement_still(msg.lin_acc, msg.info);
                 } else {
-                    self.cal.add_acc_measurement(msg.lin_acc);
+                    device.cal.add_acc_measurement(msg.lin_acc, msg.info);
                 }
             }
 
+            if device.warmup.active {
+                device.warmup.points.push(msg.ang_vel);
+                device.warmup.info.push(msg.info);
+            }
+
             if self.collect_gyro {
-                self.gyro_rate.received();
+                device.gyro_rate.received();
+                device.gyro_health.update(&msg.info, seq_modulus);
                 if self.filter_standstill {
-                    self.cal.add_gyro_measurement_still(msg.ang_vel);
+                    device.cal.add_gyro_measurement_still(msg.ang_vel, msg.info);
                 } else {
-                    self.cal.add_gyro_measurement(msg.ang_vel);
+                    device.cal.add_gyro_measurement(msg.ang_vel, msg.info);
                 }
             }
         }
 
         while let Ok(msg) = self.mag_rx.try_recv() {
+            let i = self.device_index(&msg.device);
+            let device = &mut self.devices[i];
+            let units = provider_units.unwrap_or(device.input_units);
+            let mut msg = units.normalize_mag(msg);
+            msg.field = device.cal.axis_maps.mag.apply(msg.field);
+
             if self.collect_mag {
-                self.mag_rate.received();
-                self.cal.add_mag_measurement(msg.field);
+                device.mag_rate.received();
+                device.mag_health.update(&msg.info, seq_modulus);
+                device.cal.add_mag_measurement(msg.field, msg.info);
             }
         }
 
+        let device_list: Vec<_> = self
+            .devices
+            .iter()
+            .map(|d| {
+                let points = format!(
+                    "{} / {} / {}",
+                    d.cal.gyro_measurements().len(),
+                    d.cal.acc_measurements().len(),
+                    d.cal.mag_measurements().len()
+                );
+                (d.label().to_string(), points)
+            })
+            .collect();
+        let mut selected_device = self.selected_device;
+        let mut estimate_alignment = false;
+        let mut copy_all_cal_data = false;
+        let device = &mut self.devices[self.selected_device];
+
         let modal_cal_data = Modal::new(ctx, "cal_data");
         modal_cal_data.show(|ui| {
-            let cal_data = self.cal_data.as_ref().unwrap();
+            let cal_data = device.cal_data.as_ref().unwrap();
 
             let info = cal_data.as_json_string();
 
@@ -206,6 +384,71 @@ impl eframe::App for MyApp {
                         ui.label(format!("{:.4e}", cal_data.gyro_offset.z));
                     });
                 ui.separator();
+                ui.heading("gyro scale and misalignment");
+                egui::Grid::new("grid_gyro_transf")
+                    .striped(true)
+                    .show(ui, |ui| {
+                        for r in 0..3 {
+                            for c in 0..3 {
+                                ui.label(format!("{:.4e}", cal_data.gyro_transf[(r, c)]));
+                            }
+                            ui.end_row();
+                        }
+                    });
+                ui.separator();
+                if cal_data.gyro_acc_rotation != nalgebra::Matrix3::identity() {
+                    let rotation =
+                        nalgebra::Rotation3::from_matrix_unchecked(cal_data.gyro_acc_rotation);
+                    let (roll, pitch, yaw) = rotation.euler_angles();
+                    ui.label(format!(
+                        "gyro → accel frame: {:.3}° (roll {:.3}°, pitch {:.3}°, yaw {:.3}°)",
+                        rotation.angle().to_degrees(),
+                        roll.to_degrees(),
+                        pitch.to_degrees(),
+                        yaw.to_degrees()
+                    ));
+                    ui.separator();
+                }
+                if let Some(temp_cal) = cal_data.temp_cal.as_ref() {
+                    let fitted: Vec<_> = [
+                        ("gyro", &temp_cal.gyro),
+                        ("accel", &temp_cal.acc),
+                        ("mag", &temp_cal.mag),
+                    ]
+                    .iter()
+                    .filter(|(_, curve)| curve.is_some())
+                    .map(|(name, _)| *name)
+                    .collect();
+                    ui.label(format!(
+                        "temperature drift (degree {}, ref. {:.1} °C): {}",
+                        temp_cal.degree,
+                        temp_cal.t_ref,
+                        fitted.join(", ")
+                    ));
+                    ui.separator();
+                }
+                if let Some(sensitivity) = cal_data.gyro_g_sensitivity {
+                    ui.heading("gyro g-sensitivity [°/s/g]");
+                    egui::Grid::new("grid_gyro_g_sensitivity")
+                        .striped(true)
+                        .show(ui, |ui| {
+                            let sensitivity = sensitivity * 9.80665f64.to_degrees();
+                            for r in 0..3 {
+                                for c in 0..3 {
+                                    ui.label(format!("{:.4e}", sensitivity[(r, c)]));
+                                }
+                                ui.end_row();
+                            }
+                        });
+                    ui.separator();
+                }
+                if let Some(rate) = cal_data.earth_rate {
+                    ui.label(format!(
+                        "Earth rate removed: {:.3e} {:.3e} {:.3e} rad/s",
+                        rate.x, rate.y, rate.z
+                    ));
+                    ui.separator();
+                }
                 ui.heading("accel offset");
                 egui::Grid::new("grid_acc_offset")
                     .striped(true)
@@ -215,14 +458,21 @@ impl eframe::App for MyApp {
                         ui.label(format!("{:.4e}", cal_data.acc_offset.z));
                     });
                 ui.separator();
-                ui.heading("accel scale");
-                egui::Grid::new("grid_acc_scale")
+                ui.heading("accel scale and misalignment");
+                egui::Grid::new("grid_acc_transf")
                     .striped(true)
                     .show(ui, |ui| {
-                        ui.label(format!("{:.4e}", cal_data.acc_scale.x));
-                        ui.label(format!("{:.4e}", cal_data.acc_scale.y));
-                        ui.label(format!("{:.4e}", cal_data.acc_scale.z));
+                        for r in 0..3 {
+                            for c in 0..3 {
+                                ui.label(format!("{:.4e}", cal_data.acc_transf[(r, c)]));
+                            }
+                            ui.end_row();
+                        }
                     });
+                ui.label(format!(
+                    "residual: {:.4} m/s² (g = {:.5} m/s²)",
+                    cal_data.acc_residual, cal_data.gravity
+                ));
                 ui.separator();
                 ui.heading("mag soft iron transform");
                 egui::Grid::new("grid_soft_iron")
@@ -250,6 +500,25 @@ impl eframe::App for MyApp {
                         ui.label(format!("{:.4e}", cal_data.hard_iron_bias.z));
                     });
                 ui.separator();
+                ui.label(format!("mag model: {}", cal_data.mag_model.label()));
+                ui.label(match cal_data.mag_scale {
+                    MagScale::Field => format!("field magnitude: {:.2} µT", cal_data.mag_field),
+                    MagScale::UnitSphere => "scaled to the unit sphere".to_string(),
+                });
+                if let Some(inclination) = cal_data.mag_inclination {
+                    let expected = cal_data
+                        .mag_inclination_expected
+                        .map_or("-".to_string(), |i| format!("{:.2}°", i.to_degrees()));
+                    ui.label(format!(
+                        "inclination: {:.2}° (expected {})",
+                        inclination.to_degrees(),
+                        expected
+                    ));
+                }
+                if let Some(declination) = cal_data.mag_declination {
+                    ui.label(format!("declination: {:.2}°", declination.to_degrees()));
+                }
+                ui.separator();
                 ui.heading("mag calibration quality");
                 egui::Grid::new("mag_quality").striped(true).show(ui, |ui| {
                     let color = match cal_data.mag_quality.surface_gap_error() {
@@ -286,6 +555,50 @@ impl eframe::App for MyApp {
             });
         });
 
+        if let Some(log_import) = self.log_import.as_mut() {
+            let mut open = true;
+            let mut import = false;
+            egui::Window::new("Flight Log Import")
+                .open(&mut open)
+                .show(ctx, |ui| {
+                    ui.label(&log_import.file_name);
+                    ui.separator();
+                    egui::Grid::new("grid_log_streams")
+                        .num_columns(4)
+                        .striped(true)
+                        .show(ui, |ui| {
+                            for stream in log_import.streams.iter_mut() {
+                                ui.checkbox(&mut stream.selected, &stream.name);
+                                ui.label(stream.kind.label());
+                                ui.label(format!("{}", stream.samples.len()));
+                                ui.label(format!("{:.1} s", stream.duration()));
+                                ui.end_row();
+                            }
+                        });
+                    if log_import
+                        .streams
+                        .iter()
+                        .any(|s| s.selected && s.kind == SensorKind::Mag)
+                    {
+                        ui.label(
+                            "Mag samples are fitted as logged. Samples taken with motors \
+                             running are not detected or excluded, use a motors-off log.",
+                        );
+                    }
+                    ui.separator();
+                    if ui.button("Import selected").clicked() {
+                        import = true;
+                    }
+                });
+
+            if import {
+                log_import.import_into(&mut device.cal);
+            }
+            if import || !open {
+                self.log_import = None;
+            }
+        }
+
         egui::SidePanel::left("left_panel").show(ctx, |ui| {
             menu::bar(ui, |ui| {
                 ui.menu_button("🗀  File", |ui| {
@@ -294,7 +607,7 @@ impl eframe::App for MyApp {
                             .add_filter("data", &["json"])
                             .pick_file()
                         {
-                            self.cal.load_from_file(path);
+                            device.cal.load_from_file(path);
                             ui.close_menu();
                         }
                     }
@@ -304,7 +617,19 @@ impl eframe::App for MyApp {
                             .save_file()
                         {
                             path.set_extension("json");
-                            self.cal.save_to_file(path);
+                            device.cal.save_to_file(path);
+                            ui.close_menu();
+                        }
+                    }
+                    if ui.button("✈    Import flight log").clicked() {
+                        if let Some(path) = rfd::FileDialog::new()
+                            .add_filter("flight log", &["ulg", "bin", "BIN"])
+                            .pick_file()
+                        {
+                            match LogImport::load(&path) {
+                                Ok(log_import) => self.log_import = Some(log_import),
+                                Err(err) => println!("Unable to import {:?}: {}", path, err),
+                            }
                             ui.close_menu();
                         }
                     }
@@ -316,6 +641,21 @@ impl eframe::App for MyApp {
             self.data_provider.show(ui);
             ui.separator();
 
+            ui.add_space(5.0);
+            ui.heading("Devices");
+            egui::Grid::new("devices_grid")
+                .striped(true)
+                .show(ui, |ui| {
+                    for (i, (label, points)) in device_list.iter().enumerate() {
+                        if ui.selectable_label(selected_device == i, label).clicked() {
+                            selected_device = i;
+                        }
+                        ui.label(points).on_hover_text("gyro / accel / mag points");
+                        ui.end_row();
+                    }
+                });
+            ui.separator();
+
             ui.add_space(5.0);
             ui.horizontal(|ui| {
                 ui.heading("Data Sources");
@@ -333,13 +673,22 @@ impl eframe::App for MyApp {
 
             egui::Grid::new("data_source_grid").show(ui, |ui| {
                 ui.toggle_value(&mut self.collect_gyro, "Gyro");
-                ui.label(self.gyro_rate.to_string());
+                ui.label(device.gyro_rate.to_string());
+                health_label(ui, &device.gyro_health);
+                ui.toggle_value(&mut device.show_gyro_jitter, "📊")
+                    .on_hover_text("jitter");
                 ui.end_row();
                 ui.toggle_value(&mut self.collect_acc, "Accel");
-                ui.label(self.acc_rate.to_string());
+                ui.label(device.acc_rate.to_string());
+                health_label(ui, &device.acc_health);
+                ui.toggle_value(&mut device.show_acc_jitter, "📊")
+                    .on_hover_text("jitter");
                 ui.end_row();
                 ui.toggle_value(&mut self.collect_mag, "Mag");
-                ui.label(self.mag_rate.to_string());
+                ui.label(device.mag_rate.to_string());
+                health_label(ui, &device.mag_health);
+                ui.toggle_value(&mut device.show_mag_jitter, "📊")
+                    .on_hover_text("jitter");
                 ui.end_row();
             });
 
@@ -352,36 +701,123 @@ impl eframe::App for MyApp {
                 .striped(true)
                 .show(ui, |ui| {
                     ui.label("Gyro");
-                    ui.label(format!("{}", self.cal.gyro_measurements().len()));
+                    ui.label(format!("{}", device.cal.gyro_measurements().len()));
                     if ui
                         .button(RichText::new("🗑").color(Color32::LIGHT_RED))
                         .on_hover_text("clear")
                         .clicked()
                     {
-                        self.cal.clear_gyro_measurements();
+                        device.cal.clear_gyro_measurements();
                     }
                     ui.end_row();
                     ui.label("Accel");
-                    ui.label(format!("{}", self.cal.acc_measurements().len()));
+                    ui.label(format!("{}", device.cal.acc_measurements().len()));
                     if ui
                         .button(RichText::new("🗑").color(Color32::LIGHT_RED))
                         .on_hover_text("clear")
                         .clicked()
                     {
-                        self.cal.clear_accel_measurements();
+                        device.cal.clear_accel_measurements();
                     }
                     ui.end_row();
                     ui.label("Mag");
-                    ui.label(format!("{}", self.cal.mag_measurements().len()));
+                    ui.label(format!("{}", device.cal.mag_measurements().len()));
                     if ui
                         .button(RichText::new("🗑").color(Color32::LIGHT_RED))
                         .on_hover_text("clear")
                         .clicked()
                     {
-                        self.cal.clear_mag_measurements();
+                        device.cal.clear_mag_measurements();
                     }
                 });
 
+            if device.acc_poses.0 != device.cal.acc_measurements().len() {
+                device.acc_poses = (
+                    device.cal.acc_measurements().len(),
+                    device.cal.acc_poses(),
+                );
+            }
+            let poses = &device.acc_poses.1;
+            let missing = poses::missing_directions(poses);
+            ui.label(format!(
+                "Accel poses: {} ({} distinct)",
+                poses.len(),
+                poses::distinct_count(poses)
+            ))
+            .on_hover_text("still periods, averaged to one vector each");
+            if !missing.is_empty() {
+                let missing: Vec<_> = missing.iter().map(|a| a.label()).collect();
+                ui.label(
+                    RichText::new(format!("missing up: {}", missing.join(" ")))
+                        .small()
+                        .color(Color32::YELLOW),
+                );
+            }
+
+            ui.separator();
+
+            ui.add_space(5.0);
+            ui.heading("Sensor");
+            let mut profile = device.profile;
+            egui::ComboBox::new("profile", "")
+                .selected_text(profile.map_or("Custom", |p| p.name))
+                .show_ui(ui, |ui| {
+                    ui.selectable_value(&mut profile, None, "Custom");
+                    for p in PROFILES {
+                        ui.selectable_value(&mut profile, Some(p), p.name);
+                    }
+                });
+            if profile != device.profile {
+                device.profile = profile;
+                if let Some(p) = profile {
+                    device.apply_profile(&p);
+                }
+            }
+            if let Some(p) = device.profile {
+                ui.label(
+                    RichText::new(format!(
+                        "noise: acc {} µg/√Hz, gyro {} °/s/√Hz",
+                        p.acc_noise, p.gyro_noise
+                    ))
+                    .small(),
+                );
+            }
+            ui.separator();
+
+            ui.add_space(5.0);
+            ui.heading("Units");
+            match self.data_provider.input_units() {
+                Some(units) => {
+                    ui.label(format!(
+                        "{}, {}, {} (set by provider)",
+                        units.gyro.label(),
+                        units.acc.label(),
+                        units.mag.label()
+                    ));
+                }
+                None => units_editor(ui, &mut device.input_units),
+            }
+            ui.separator();
+
+            ui.add_space(5.0);
+            ui.heading("Axes");
+            egui::Grid::new("axes_grid").num_columns(3).show(ui, |ui| {
+                ui.label("Gyro");
+                if axis_map_editor(ui, "gyro_axes", &mut device.cal.axis_maps.gyro) {
+                    device.cal.clear_gyro_measurements();
+                }
+                ui.end_row();
+                ui.label("Accel");
+                if axis_map_editor(ui, "acc_axes", &mut device.cal.axis_maps.acc) {
+                    device.cal.clear_accel_measurements();
+                }
+                ui.end_row();
+                ui.label("Mag");
+                if axis_map_editor(ui, "mag_axes", &mut device.cal.axis_maps.mag) {
+                    device.cal.clear_mag_measurements();
+                }
+                ui.end_row();
+            });
             ui.separator();
 
             ui.add_space(5.0);
@@ -391,48 +827,348 @@ impl eframe::App for MyApp {
 
             ui.add_space(5.0);
             ui.heading("Calibration");
+            egui::ComboBox::new("gyro_model", "Gyro model")
+                .selected_text(device.cal.gyro_model.label())
+                .show_ui(ui, |ui| {
+                    for model in GyroModel::ALL {
+                        ui.selectable_value(&mut device.cal.gyro_model, model, model.label());
+                    }
+                });
+            match device.cal.gyro_model {
+                GyroModel::Offset => (),
+                GyroModel::GravityReference => {
+                    ui.label(
+                        RichText::new("tilt between static poses, collect gyro and accel").small(),
+                    );
+                }
+                GyroModel::KnownTurns => {
+                    ui.add(
+                        egui::DragValue::new(&mut device.cal.gyro_turns)
+                            .range(0.25..=100.0)
+                            .speed(0.25)
+                            .suffix(" turns"),
+                    )
+                    .on_hover_text("per rotation, rotate about each axis and rest in between");
+                }
+                GyroModel::Turntable => {
+                    let mut rate = device.cal.turntable_rate.to_degrees();
+                    ui.add(
+                        egui::DragValue::new(&mut rate)
+                            .range(1.0..=2000.0)
+                            .suffix(" °/s"),
+                    )
+                    .on_hover_text("turntable rate, spin about each axis and stop in between");
+                    device.cal.turntable_rate = rate.to_radians();
+                }
+            }
+            ui.checkbox(&mut device.cal.gyro_g_sensitivity, "Gyro g-sensitivity")
+                .on_hover_text("still in at least six orientations, collect gyro and accel");
+            egui::ComboBox::new("acc_model", "Accel model")
+                .selected_text(device.cal.acc_model.label())
+                .show_ui(ui, |ui| {
+                    for model in AccModel::ALL {
+                        ui.selectable_value(&mut device.cal.acc_model, model, model.label());
+                    }
+                });
+            if device.cal.acc_model == AccModel::SixFace {
+                if ui
+                    .button("🧭 Six-face wizard")
+                    .on_hover_text("guides through the six faces, clears the accel points")
+                    .clicked()
+                {
+                    device.cal.clear_accel_measurements();
+                    device.wizard = Some(SixFaceWizard::new(WIZARD_DURATION));
+                }
+                let missing = device.cal.missing_acc_faces();
+                if !device.cal.acc_measurements().is_empty() && !missing.is_empty() {
+                    let missing: Vec<_> = missing.iter().map(|a| a.label()).collect();
+                    ui.label(
+                        RichText::new(format!(
+                            "⚠ faces missing: {}, their axes stay uncalibrated",
+                            missing.join(" ")
+                        ))
+                        .small()
+                        .color(Color32::YELLOW),
+                    );
+                }
+            }
+            ui.checkbox(&mut device.cal.temp_comp, "Temperature compensation")
+                .on_hover_text("fit the drift over a temperature sweep, samples need temperature");
+            if device.cal.temp_comp {
+                ui.horizontal(|ui| {
+                    ui.add(
+                        egui::DragValue::new(&mut device.cal.temp_degree)
+                            .range(1..=3)
+                            .prefix("degree "),
+                    );
+                    ui.checkbox(&mut device.cal.temp_scale, "scale")
+                        .on_hover_text("accel and mag, needs the sweep in several orientations");
+                    ui.toggle_value(&mut device.show_temperature, "📈");
+                });
+                if let Some(problem) = device.cal.temp_problem() {
+                    ui.label(
+                        RichText::new(format!("⚠ no drift fitted: {}", problem))
+                            .small()
+                            .color(Color32::YELLOW),
+                    );
+                }
+            }
+            egui::ComboBox::new("mag_model", "Mag model")
+                .selected_text(device.cal.mag_model.label())
+                .show_ui(ui, |ui| {
+                    for model in MagModel::ALL {
+                        ui.selectable_value(&mut device.cal.mag_model, model, model.label());
+                    }
+                });
+            egui::ComboBox::new("mag_scale", "Mag output")
+                .selected_text(device.cal.mag_scale.label())
+                .show_ui(ui, |ui| {
+                    for scale in MagScale::ALL {
+                        ui.selectable_value(&mut device.cal.mag_scale, scale, scale.label());
+                    }
+                });
+            ui.checkbox(&mut device.cal.local_gravity, "Local gravity")
+                .on_hover_text("scale the accel to the WGS84 normal gravity instead of 9.80665");
+            ui.checkbox(&mut device.cal.earth_rate, "Remove Earth rate")
+                .on_hover_text("for high grade gyros, gyro offset from a single static pose with accel and mag");
+            ui.checkbox(&mut device.cal.wmm, "World Magnetic Model")
+                .on_hover_text("expected mag field magnitude, inclination and declination");
+            if device.cal.local_gravity || device.cal.earth_rate || device.cal.wmm {
+                let mut latitude = device.cal.latitude.to_degrees();
+                let mut longitude = device.cal.longitude.to_degrees();
+                let mut declination = device.cal.declination.to_degrees();
+                ui.horizontal(|ui| {
+                    ui.add(
+                        egui::DragValue::new(&mut latitude)
+                            .range(-90.0..=90.0)
+                            .speed(0.1)
+                            .prefix("lat ")
+                            .suffix("°"),
+                    );
+                    if device.cal.wmm {
+                        ui.add(
+                            egui::DragValue::new(&mut longitude)
+                                .range(-180.0..=180.0)
+                                .speed(0.1)
+                                .prefix("lon ")
+                                .suffix("°"),
+                        );
+                    }
+                    if device.cal.local_gravity || device.cal.wmm {
+                        ui.add(
+                            egui::DragValue::new(&mut device.cal.height)
+                                .range(-500.0..=10000.0)
+                                .prefix("h ")
+                                .suffix(" m"),
+                        )
+                        .on_hover_text("height above the WGS84 ellipsoid");
+                    }
+                });
+                ui.horizontal(|ui| {
+                    if device.cal.wmm {
+                        ui.add(
+                            egui::DragValue::new(&mut device.cal.year)
+                                .range(2025.0..=2030.0)
+                                .speed(0.01)
+                                .max_decimals(2)
+                                .prefix("year "),
+                        )
+                        .on_hover_text("decimal year, the embedded WMM2025 is valid until 2030");
+                    } else if device.cal.earth_rate {
+                        ui.add(
+                            egui::DragValue::new(&mut declination)
+                                .range(-180.0..=180.0)
+                                .speed(0.1)
+                                .prefix("decl ")
+                                .suffix("°"),
+                        )
+                        .on_hover_text("magnetic declination, positive east");
+                    }
+                });
+                device.cal.latitude = latitude.to_radians();
+                device.cal.longitude = longitude.to_radians();
+                device.cal.declination = declination.to_radians();
+            }
+            if device.cal.local_gravity {
+                ui.label(RichText::new(format!("g = {:.5} m/s²", device.cal.gravity())).small());
+            }
+            if let Some(field) = device.cal.expected_field() {
+                ui.label(
+                    RichText::new(format!(
+                        "F = {:.2} µT, I = {:.2}°, D = {:.2}°",
+                        field.intensity(),
+                        field.inclination().to_degrees(),
+                        field.declination().to_degrees()
+                    ))
+                    .small(),
+                );
+            }
+            if device.cal.earth_rate {
+                if ui
+                    .button("🧭 Gyrocompass")
+                    .on_hover_text("heading from the Earth rate, the IMU must not move")
+                    .clicked()
+                {
+                    device.gyrocompass = device.cal.gyrocompass();
+                    if device.gyrocompass.is_none() {
+                        println!("Gyrocompassing needs gyro and accel samples");
+                    }
+                }
+                if let Some((heading, mag_heading)) = device.gyrocompass {
+                    let mag_heading =
+                        mag_heading.map_or("-".to_string(), |h| format!("{:.1}°", h.to_degrees()));
+                    ui.label(format!(
+                        "heading {:.1}° (mag {})",
+                        heading.to_degrees(),
+                        mag_heading
+                    ));
+                }
+            }
             if ui
                 .button(RichText::new("Calibrate now").color(Color32::LIGHT_GREEN))
                 .clicked()
             {
-                self.cal_data = Some(self.cal.calibrate());
+                device.cal_data = Some(device.cal.calibrate());
                 modal_cal_data.open();
             }
-            if let Some(cal_data) = self.cal_data.as_ref() {
+            if let Some(cal_data) = device.cal_data.as_ref() {
                 if ui.button("🗐 copy as json").clicked() {
                     ui.output_mut(|w| w.copied_text = cal_data.as_json_string())
                 }
             }
+            if device_list.len() > 1 && ui.button("🗐 copy all devices as json").clicked() {
+                copy_all_cal_data = true;
+            }
             ui.separator();
 
+            if device_list.len() > 1 {
+                ui.add_space(5.0);
+                ui.heading("Alignment");
+                ui.horizontal(|ui| {
+                    ui.label("relative to");
+                    egui::ComboBox::new("align_reference", "")
+                        .selected_text(
+                            &device_list[self.align_reference.min(device_list.len() - 1)].0,
+                        )
+                        .show_ui(ui, |ui| {
+                            for (i, (label, _)) in device_list.iter().enumerate() {
+                                ui.selectable_value(&mut self.align_reference, i, label);
+                            }
+                        });
+                    if ui
+                        .button("Estimate")
+                        .on_hover_text("from simultaneous gyro (moving) and accel (static) samples")
+                        .clicked()
+                    {
+                        estimate_alignment = true;
+                    }
+                });
+                if let Some((reference, other, alignment)) = self.alignment.as_ref() {
+                    let angles = alignment.euler_angles_deg();
+                    ui.label(format!("{} → {}", other, reference));
+                    ui.label(format!(
+                        "roll {:.2}°  pitch {:.2}°  yaw {:.2}°",
+                        angles.x, angles.y, angles.z
+                    ));
+                    ui.label(
+                        RichText::new(format!(
+                            "residual {:.2}° ({} gyro, {} accel pairs)",
+                            alignment.residual.to_degrees(),
+                            alignment.gyro_pairs,
+                            alignment.acc_pairs
+                        ))
+                        .small(),
+                    );
+                    if ui.button("🗐 copy rotation as json").clicked() {
+                        ui.output_mut(|w| {
+                            w.copied_text =
+                                serde_json::to_string_pretty(alignment.rotation.matrix()).unwrap()
+                        })
+                    }
+                }
+                ui.separator();
+            }
+
             ui.add_space(5.0);
             ui.heading("View");
             ui.toggle_value(&mut self.show_gyro, "Gyro");
             ui.toggle_value(&mut self.show_acc, "Accel");
             ui.toggle_value(&mut self.show_mag, "Mag");
+            ui.toggle_value(&mut device.show_allan, "Allan deviation")
+                .on_hover_text("noise parameters from a long static recording");
+            ui.toggle_value(&mut device.show_warmup, "Gyro warm-up")
+                .on_hover_text("bias settling after power-on");
 
             ui.separator();
         });
 
         egui::CentralPanel::default().show(ctx, |_ui| {
+            if wizard_window(ctx, &mut device.wizard) {
+                device.cal_data = Some(device.cal.calibrate());
+                modal_cal_data.open();
+            }
+            jitter_window(
+                ctx,
+                "Gyro Jitter",
+                &mut device.show_gyro_jitter,
+                &mut device.gyro_health,
+            );
+            jitter_window(
+                ctx,
+                "Accel Jitter",
+                &mut device.show_acc_jitter,
+                &mut device.acc_health,
+            );
+            jitter_window(
+                ctx,
+                "Mag Jitter",
+                &mut device.show_mag_jitter,
+                &mut device.mag_health,
+            );
+            allan_window(
+                ctx,
+                &mut device.show_allan,
+                &mut device.allan_sensor,
+                &mut device.allan,
+                &device.cal,
+            );
+            warmup_window(
+                ctx,
+                &mut device.show_warmup,
+                &mut device.warmup_tolerance,
+                &mut device.warmup,
+            );
+            temperature_window(
+                ctx,
+                &mut device.show_temperature,
+                &mut device.temperature_sensor,
+                &mut device.temperature_bins,
+                &device.cal,
+                device.cal_data.as_ref(),
+            );
+
             // gyro plot
             if self.show_gyro {
                 plot_window(
                     ctx,
-                    &mut self.gyro_plot_type,
+                    &mut device.gyro_plot_type,
                     "Gyro",
                     "rad/s",
-                    self.cal.gyro_measurements(),
+                    device.cal.gyro_measurements(),
+                    &device.cal,
+                    SensorKind::Gyro,
                 );
 
-                if self.cal_data.is_some() {
-                    let measurements_with_cal = self.cal.gyro_measurements_with_cal();
+                if device.cal_data.is_some() {
+                    let measurements_with_cal = device.cal.gyro_measurements_with_cal();
                     plot_window(
                         ctx,
-                        &mut self.gyro_cal_plot_type,
+                        &mut device.gyro_cal_plot_type,
                         "Gyro (calibrated)",
                         "rad/s",
                         &measurements_with_cal,
+                        &device.cal,
+                        SensorKind::Gyro,
                     );
                 }
             }
@@ -441,20 +1177,24 @@ impl eframe::App for MyApp {
             if self.show_acc {
                 plot_window(
                     ctx,
-                    &mut self.acc_plot_type,
+                    &mut device.acc_plot_type,
                     "Accel",
                     "m/s²",
-                    self.cal.acc_measurements(),
+                    device.cal.acc_measurements(),
+                    &device.cal,
+                    SensorKind::Acc,
                 );
 
-                if self.cal_data.is_some() {
-                    let measurements_with_cal = self.cal.acc_measurements_with_cal();
+                if device.cal_data.is_some() {
+                    let measurements_with_cal = device.cal.acc_measurements_with_cal();
                     plot_window(
                         ctx,
-                        &mut self.acc_cal_plot_type,
+                        &mut device.acc_cal_plot_type,
                         "Accel (calibrated)",
                         "m/s²",
                         &measurements_with_cal,
+                        &device.cal,
+                        SensorKind::Acc,
                     );
                 }
             }
@@ -463,25 +1203,51 @@ impl eframe::App for MyApp {
             if self.show_mag {
                 plot_window(
                     ctx,
-                    &mut self.mag_plot_type,
+                    &mut device.mag_plot_type,
                     "Mag",
                     "µT",
-                    self.cal.mag_measurements(),
+                    device.cal.mag_measurements(),
+                    &device.cal,
+                    SensorKind::Mag,
                 );
 
-                if self.cal_data.is_some() {
-                    let measurements_with_cal = self.cal.mag_measurements_with_cal();
+                if let Some(cal_data) = device.cal_data.as_ref() {
+                    let measurements_with_cal = device.cal.mag_measurements_with_cal();
                     plot_window(
                         ctx,
-                        &mut self.mag_cal_plot_type,
+                        &mut device.mag_cal_plot_type,
                         "Mag (calibrated)",
-                        "µT",
+                        cal_data.mag_scale.unit(),
                         &measurements_with_cal,
+                        &device.cal,
+                        SensorKind::Mag,
                     );
                 }
             }
         });
 
+        if copy_all_cal_data {
+            let json = self.all_cal_data_json();
+            ctx.output_mut(|w| w.copied_text = json);
+        }
+
+        if estimate_alignment {
+            let reference = &self.devices[self.align_reference.min(self.devices.len() - 1)];
+            let other = &self.devices[self.selected_device];
+            self.alignment = match align(&reference.cal, &other.cal) {
+                Some(alignment) => Some((
+                    reference.label().to_string(),
+                    other.label().to_string(),
+                    alignment,
+                )),
+                None => {
+                    println!("Not enough simultaneous samples in different directions for the alignment, move or turn the body");
+                    None
+                }
+            };
+        }
+        self.selected_device = selected_device;
+
         ctx.request_repaint();
     }
 }
@@ -492,11 +1258,19 @@ fn plot_window(
     window_title: &str,
     unit: &str,
     data: &[nalgebra::Vector3<f64>],
+    cal: &Cal,
+    sensor: SensorKind,
 ) {
+    let info = match sensor {
+        SensorKind::Gyro => cal.gyro_info(),
+        SensorKind::Acc => cal.acc_info(),
+        SensorKind::Mag => cal.mag_info(),
+    };
     egui::Window::new(window_title).show(ctx, |ui| {
         ui.horizontal(|ui| {
             ui.selectable_value(plot_type, PlotType::Scatter, "Scatter");
             ui.selectable_value(plot_type, PlotType::Histogram(10), "Histogram");
+            ui.selectable_value(plot_type, PlotType::Psd(512, None), "PSD");
 
             match plot_type {
                 PlotType::Histogram(buckets) => {
@@ -504,6 +1278,37 @@ fn plot_window(
                     ui.add(egui::DragValue::new(buckets));
                     *buckets = (*buckets).clamp(1, 30);
                 }
+                PlotType::Psd(segment, spectrum) => {
+                    egui::ComboBox::new(format!("{window_title}_segment"), "Segment")
+                        .selected_text(format!("{segment}"))
+                        .show_ui(ui, |ui| {
+                            for len in PSD_SEGMENTS {
+                                if ui
+                                    .selectable_value(segment, len, format!("{len}"))
+                                    .changed()
+                                {
+                                    *spectrum = None;
+                                }
+                            }
+                        })
+                        .response
+                        .on_hover_text(
+                            "samples per FFT, longer gives a finer frequency resolution",
+                        );
+                    if ui
+                        .button("Compute")
+                        .on_hover_text("from the still periods of the collected samples")
+                        .clicked()
+                    {
+                        *spectrum = psd::analyze(data, info, &cal.still_ranges(sensor), *segment);
+                        if spectrum.is_none() {
+                            println!(
+                                "PSD needs still periods of at least {} timestamped samples",
+                                segment
+                            );
+                        }
+                    }
+                }
                 _ => (),
             }
         });
@@ -565,7 +1370,584 @@ fn plot_window(
                         plot_ui.bar_chart(egui_plot::BarChart::new(boxes).name(label));
                     }
                 }),
+            PlotType::Psd(segment, spectrum) => {
+                match spectrum.as_ref() {
+                    Some(spectrum) => {
+                        ui.label(format!(
+                            "noise density: X {:.3e}  Y {:.3e}  Z {:.3e} {}/√Hz",
+                            spectrum.density[0], spectrum.density[1], spectrum.density[2], unit
+                        ));
+                        if !spectrum.peaks.is_empty() {
+                            let peaks: Vec<_> = spectrum
+                                .peaks
+                                .iter()
+                                .map(|p| {
+                                    format!("{:.1} Hz ({})", p.frequency, ["X", "Y", "Z"][p.axis])
+                                })
+                                .collect();
+                            ui.label(
+                                RichText::new(format!("⚠ vibration peaks: {}", peaks.join(", ")))
+                                    .small()
+                                    .color(Color32::YELLOW),
+                            );
+                        }
+                    }
+                    None => {
+                        ui.label(format!(
+                            "no result, needs still periods of at least {} timestamped samples",
+                            segment
+                        ));
+                    }
+                }
+
+                egui_plot::Plot::new(window_title)
+                    .allow_zoom(true)
+                    .allow_drag(true)
+                    .allow_scroll(false)
+                    .allow_boxed_zoom(true)
+                    .x_axis_label("frequency [Hz]")
+                    .y_axis_label(format!("log10 density [{}/√Hz]", unit))
+                    .legend(Legend::default())
+                    .show(ui, |plot_ui| {
+                        let Some(spectrum) = spectrum.as_ref() else {
+                            return;
+                        };
+                        for (axis, label) in ["X", "Y", "Z"].iter().enumerate() {
+                            plot_ui.line(
+                                egui_plot::Line::new(
+                                    spectrum
+                                        .frequencies
+                                        .iter()
+                                        .zip(&spectrum.psd[axis])
+                                        .skip(1)
+                                        .map(|(f, p)| [*f, p.sqrt().log10()])
+                                        .collect::<Vec<_>>(),
+                                )
+                                .name(label),
+                            );
+                        }
+                        plot_ui.points(
+                            egui_plot::Points::new(
+                                spectrum
+                                    .peaks
+                                    .iter()
+                                    .map(|p| [p.frequency, p.density.log10()])
+                                    .collect::<Vec<_>>(),
+                            )
+                            .shape(egui_plot::MarkerShape::Down)
+                            .radius(5.0)
+                            .color(Color32::YELLOW)
+                            .name("peaks"),
+                        );
+                    })
+            }
+        }
+    });
+}
+
+fn units_editor(ui: &mut egui::Ui, units: &mut InputUnits) {
+    egui::Grid::new("units_grid").num_columns(3).show(ui, |ui| {
+        ui.label("Gyro");
+        egui::ComboBox::new("gyro_unit", "")
+            .selected_text(units.gyro.label())
+            .show_ui(ui, |ui| {
+                for unit in GyroUnit::ALL {
+                    ui.selectable_value(&mut units.gyro, unit, unit.label());
+                }
+            });
+        ui.add(lsb_drag_value(&mut units.gyro_lsb))
+            .on_hover_text("LSB scale, 1 if not sent as raw counts");
+        ui.end_row();
+        ui.label("Accel");
+        egui::ComboBox::new("acc_unit", "")
+            .selected_text(units.acc.label())
+            .show_ui(ui, |ui| {
+                for unit in AccUnit::ALL {
+                    ui.selectable_value(&mut units.acc, unit, unit.label());
+                }
+            });
+        ui.add(lsb_drag_value(&mut units.acc_lsb))
+            .on_hover_text("LSB scale, 1 if not sent as raw counts");
+        ui.end_row();
+        ui.label("Mag");
+        egui::ComboBox::new("mag_unit", "")
+            .selected_text(units.mag.label())
+            .show_ui(ui, |ui| {
+                for unit in MagUnit::ALL {
+                    ui.selectable_value(&mut units.mag, unit, unit.label());
+                }
+            });
+        ui.add(lsb_drag_value(&mut units.mag_lsb))
+            .on_hover_text("LSB scale, 1 if not sent as raw counts");
+        ui.end_row();
+    });
+}
+
+/// Preset orientation and per axis source of a sensor. Returns true if the map changed,
+/// previously collected points are in the old frame then.
+fn axis_map_editor(ui: &mut egui::Ui, id: &str, map: &mut AxisMap) -> bool {
+    let before = *map;
+    egui::ComboBox::new(id, "")
+        .selected_text(map.label())
+        .show_ui(ui, |ui| {
+            for preset in AxisMap::presets() {
+                ui.selectable_value(map, preset, preset.label());
+            }
+        });
+    ui.horizontal(|ui| {
+        for (output, name) in ["x", "y", "z"].iter().enumerate() {
+            let mut axis = map.axes[output];
+            egui::ComboBox::new(format!("{}_{}", id, name), *name)
+                .width(40.0)
+                .selected_text(axis.label())
+                .show_ui(ui, |ui| {
+                    for a in Axis::ALL {
+                        ui.selectable_value(&mut axis, a, a.label());
+                    }
+                });
+            if axis != map.axes[output] {
+                map.set(output, axis);
+            }
+        }
+        if !map.is_proper() {
+            ui.label(RichText::new("⚠").color(Color32::YELLOW))
+                .on_hover_text("left-handed, the sensor frame is mirrored");
+        }
+    });
+    *map != before
+}
+
+fn lsb_drag_value(lsb: &mut f64) -> egui::DragValue<'_> {
+    egui::DragValue::new(lsb)
+        .prefix("× ")
+        .speed(1e-6)
+        .range(0.0..=f64::MAX)
+        .max_decimals(9)
+}
+
+fn health_label(ui: &mut egui::Ui, health: &StreamHealth) {
+    let color = match health.drop_rate() {
+        r if r > 5.0 => Color32::RED,
+        r if r > 0.5 => Color32::YELLOW,
+        _ => ui.visuals().text_color(),
+    };
+    ui.label(RichText::new(health.summary()).small().color(color));
+}
+
+/// Returns true if the user asks to calibrate after the last face.
+fn wizard_window(ctx: &egui::Context, wizard: &mut Option<SixFaceWizard>) -> bool {
+    let Some(w) = wizard.as_mut() else {
+        return false;
+    };
+    let mut open = true;
+    let mut calibrate = false;
+
+    egui::Window::new("Six-Face Wizard")
+        .open(&mut open)
+        .show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                for (i, face) in FACES.iter().enumerate() {
+                    let text = RichText::new(face.label());
+                    let text = if w.skipped.contains(face) {
+                        text.color(Color32::YELLOW)
+                    } else if i < w.step {
+                        text.color(Color32::GREEN)
+                    } else if i == w.step {
+                        text.strong()
+                    } else {
+                        text.weak()
+                    };
+                    ui.label(text);
+                }
+            });
+            ui.separator();
+
+            match w.face() {
+                Some(face) => {
+                    ui.heading(format!("Place the device with {} up", face.label()));
+                    let status = match w.state() {
+                        WizardState::WrongOrientation(Some(up)) => {
+                            format!("currently {} is up", up.label())
+                        }
+                        WizardState::WrongOrientation(None) => "tilted".to_string(),
+                        WizardState::Moving => "hold still".to_string(),
+                        WizardState::Collecting => "collecting".to_string(),
+                        WizardState::Done => String::new(),
+                    };
+                    ui.label(status);
+                    ui.add(egui::ProgressBar::new(w.progress()).show_percentage());
+                    ui.add(
+                        egui::Slider::new(&mut w.duration, 1.0..=30.0)
+                            .suffix(" s")
+                            .text("per face"),
+                    );
+                    if ui.button("skip face").clicked() {
+                        w.skip();
+                    }
+                }
+                None => {
+                    ui.heading("All faces done");
+                    if !w.skipped.is_empty() {
+                        let skipped: Vec<_> = w.skipped.iter().map(|a| a.label()).collect();
+                        ui.label(
+                            RichText::new(format!(
+                                "⚠ skipped {}, their axes stay uncalibrated",
+                                skipped.join(" ")
+                            ))
+                            .color(Color32::YELLOW),
+                        );
+                    }
+                    if ui
+                        .button(RichText::new("Calibrate now").color(Color32::LIGHT_GREEN))
+                        .clicked()
+                    {
+                        calibrate = true;
+                    }
+                }
+            }
+        });
+
+    if !open || calibrate {
+        *wizard = None;
+    }
+    calibrate
+}
+
+fn allan_window(
+    ctx: &egui::Context,
+    open: &mut bool,
+    sensor: &mut SensorKind,
+    analysis: &mut Option<AllanAnalysis>,
+    cal: &Cal,
+) {
+    egui::Window::new("Allan Deviation")
+        .open(open)
+        .show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                for kind in [SensorKind::Gyro, SensorKind::Acc] {
+                    if ui.selectable_value(sensor, kind, kind.label()).changed() {
+                        *analysis = None;
+                    }
+                }
+                if ui
+                    .button("Compute")
+                    .on_hover_text("from the collected samples, the sensor must not move")
+                    .clicked()
+                {
+                    *analysis = match sensor {
+                        SensorKind::Acc => allan::analyze(cal.acc_measurements(), cal.acc_info()),
+                        _ => allan::analyze(cal.gyro_measurements(), cal.gyro_info()),
+                    };
+                    if analysis.is_none() {
+                        println!("Allan deviation needs a longer, timestamped recording");
+                    }
+                }
+            });
+
+            let Some(analysis) = analysis.as_ref() else {
+                ui.label("no result");
+                return;
+            };
+            let unit = match sensor {
+                SensorKind::Acc => "m/s²",
+                _ => "rad/s",
+            };
+            let value = |v: Option<f64>| v.map_or("-".to_string(), |v| format!("{:.3e}", v));
+            ui.label(
+                RichText::new(format!(
+                    "sample period {:.3} ms, τ up to {:.1} s",
+                    analysis.period * 1e3,
+                    analysis.taus.last().unwrap()
+                ))
+                .small(),
+            );
+
+            egui::Grid::new("allan_params")
+                .striped(true)
+                .show(ui, |ui| {
+                    ui.label("");
+                    ui.label(format!("random walk [{}/√Hz]", unit));
+                    ui.label(format!("bias instability [{}]", unit));
+                    ui.label(format!("rate random walk [{}·√Hz]", unit));
+                    ui.end_row();
+                    for (axis, params) in ["X", "Y", "Z"].iter().zip(&analysis.params) {
+                        ui.label(*axis);
+                        ui.label(value(params.random_walk));
+                        ui.label(value(params.bias_instability));
+                        ui.label(value(params.rate_random_walk));
+                        ui.end_row();
+                    }
+                });
+            if ui.button("🗐 copy as json").clicked() {
+                ui.output_mut(|w| {
+                    w.copied_text = serde_json::to_string_pretty(&analysis.params).unwrap()
+                })
+            }
+
+            egui_plot::Plot::new("allan")
+                .allow_zoom(true)
+                .allow_drag(true)
+                .allow_scroll(false)
+                .x_axis_label("log10 τ [s]")
+                .y_axis_label(format!("log10 σ [{}]", unit))
+                .legend(Legend::default())
+                .show(ui, |plot_ui| {
+                    for (axis, name) in ["X", "Y", "Z"].iter().enumerate() {
+                        plot_ui.line(
+                            egui_plot::Line::new(
+                                analysis
+                                    .taus
+                                    .iter()
+                                    .zip(&analysis.adev[axis])
+                                    .filter(|(_, adev)| **adev > 0.0)
+                                    .map(|(tau, adev)| [tau.log10(), adev.log10()])
+                                    .collect::<Vec<_>>(),
+                            )
+                            .name(*name),
+                        );
+                    }
+                });
+        });
+}
+
+fn warmup_window(
+    ctx: &egui::Context,
+    open: &mut bool,
+    tolerance: &mut f64,
+    recording: &mut WarmupRecording,
+) {
+    egui::Window::new("Gyro Warm-up")
+        .open(open)
+        .show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                if ui
+                    .button("⟲ restart")
+                    .on_hover_text(
+                        "starts a new warm-up recording right after power-on, the calibration samples are kept",
+                    )
+                    .clicked()
+                {
+                    recording.points.clear();
+                    recording.info.clear();
+                    recording.active = true;
+                }
+                ui.toggle_value(&mut recording.active, "⏺ record");
+                ui.label(format!("{} samples", recording.points.len()));
+                let mut tolerance_deg = tolerance.to_degrees();
+                ui.add(
+                    egui::DragValue::new(&mut tolerance_deg)
+                        .range(0.0001..=1.0)
+                        .speed(0.001)
+                        .prefix("tolerance ")
+                        .suffix(" °/s"),
+                );
+                *tolerance = tolerance_deg.to_radians();
+            });
+
+            let Some(warmup) =
+                warmup::analyze(&recording.points, &recording.info, WARMUP_WINDOW)
+            else {
+                ui.label("keep the gyro still and record from power-on");
+                return;
+            };
+            let duration = warmup.rolling.last().unwrap().0;
+            let settle_time = warmup.fit.settle_time(*tolerance);
+            ui.label(format!(
+                "settles within {:.4} °/s after {:.0} s (τ {:.0} / {:.0} / {:.0} s)",
+                tolerance.to_degrees(),
+                settle_time,
+                warmup.fit.time_constant.x,
+                warmup.fit.time_constant.y,
+                warmup.fit.time_constant.z
+            ));
+            if settle_time > duration {
+                ui.label(
+                    RichText::new(format!("⚠ not settled yet, recorded {:.0} s", duration))
+                        .small()
+                        .color(Color32::YELLOW),
+                );
+            }
+
+            egui_plot::Plot::new("warmup")
+                .allow_zoom(true)
+                .allow_drag(true)
+                .allow_scroll(false)
+                .x_axis_label("time [s]")
+                .y_axis_label("bias [rad/s]")
+                .legend(Legend::default())
+                .show(ui, |plot_ui| {
+                    for (axis, name) in ["X", "Y", "Z"].iter().enumerate() {
+                        plot_ui.points(
+                            egui_plot::Points::new(
+                                warmup
+                                    .rolling
+                                    .iter()
+                                    .map(|(t, b)| [*t, b[axis]])
+                                    .collect::<Vec<_>>(),
+                            )
+                            .radius(2.0)
+                            .name(*name),
+                        );
+                        plot_ui.line(
+                            egui_plot::Line::new(
+                                (0..=200)
+                                    .map(|i| {
+                                        let t = duration * i as f64 / 200.0;
+                                        [t, warmup.fit.bias_at(t)[axis]]
+                                    })
+                                    .collect::<Vec<_>>(),
+                            )
+                            .name(format!("{} fit", name)),
+                        );
+                    }
+                    plot_ui.vline(egui_plot::VLine::new(settle_time).name("settled"));
+                });
+        });
+}
+
+fn temperature_window(
+    ctx: &egui::Context,
+    open: &mut bool,
+    sensor: &mut SensorKind,
+    bins: &mut Option<Vec<TempBin>>,
+    cal: &Cal,
+    cal_data: Option<&CalData>,
+) {
+    egui::Window::new("Temperature").open(open).show(ctx, |ui| {
+        ui.horizontal(|ui| {
+            for kind in SensorKind::ALL {
+                if ui.selectable_value(sensor, kind, kind.label()).changed() {
+                    *bins = None;
+                }
+            }
+            if ui
+                .button("Compute")
+                .on_hover_text("from the still periods of the collected samples")
+                .clicked()
+            {
+                *bins = Some(temp_comp::bin(&cal.temperature_poses(*sensor)));
+            }
+        });
+
+        let Some(bins) = bins.as_ref().filter(|b| !b.is_empty()) else {
+            ui.label("no still periods with temperature");
+            return;
+        };
+        let temp_cal = cal_data.and_then(|c| c.temp_cal.as_ref());
+        let curve = temp_cal.and_then(|t| match sensor {
+            SensorKind::Gyro => t.gyro.as_ref(),
+            SensorKind::Acc => t.acc.as_ref(),
+            SensorKind::Mag => t.mag.as_ref(),
+        });
+
+        egui_plot::Plot::new("temperature")
+            .allow_zoom(true)
+            .allow_drag(true)
+            .allow_scroll(false)
+            .x_axis_label("temperature [°C]")
+            .y_axis_label("deviation from still period mean")
+            .legend(Legend::default())
+            .show(ui, |plot_ui| {
+                for (axis, name) in ["X", "Y", "Z"].iter().enumerate() {
+                    plot_ui.points(
+                        egui_plot::Points::new(
+                            bins.iter()
+                                .map(|b| [b.temperature, b.mean[axis]])
+                                .collect::<Vec<_>>(),
+                        )
+                        .radius(2.0)
+                        .name(*name),
+                    );
+                }
+                let (Some(temp_cal), Some(curve)) = (temp_cal, curve) else {
+                    return;
+                };
+                let t_min = bins.first().unwrap().temperature;
+                let t_max = bins.last().unwrap().temperature;
+                let drift: Vec<_> = (0..=100)
+                    .map(|i| {
+                        let t = t_min + (t_max - t_min) * i as f64 / 100.0;
+                        (t, curve.bias_drift(t, temp_cal.t_ref))
+                    })
+                    .collect();
+                let drift_mean = drift.iter().map(|(_, d)| d).sum::<nalgebra::Vector3<f64>>()
+                    / drift.len() as f64;
+                for (axis, name) in ["X fit", "Y fit", "Z fit"].iter().enumerate() {
+                    plot_ui.line(
+                        egui_plot::Line::new(
+                            drift
+                                .iter()
+                                .map(|(t, d)| [*t, d[axis] - drift_mean[axis]])
+                                .collect::<Vec<_>>(),
+                        )
+                        .name(*name),
+                    );
+                }
+            });
+    });
+}
+
+fn jitter_window(
+    ctx: &egui::Context,
+    window_title: &str,
+    open: &mut bool,
+    health: &mut StreamHealth,
+) {
+    egui::Window::new(window_title).open(open).show(ctx, |ui| {
+        egui::Grid::new(window_title).striped(true).show(ui, |ui| {
+            ui.label("Received");
+            ui.label(format!("{}", health.received));
+            ui.end_row();
+            ui.label("Dropped");
+            ui.label(format!("{} ({:.2}%)", health.dropped, health.drop_rate()));
+            ui.end_row();
+            ui.label("Duplicates");
+            ui.label(format!("{}", health.duplicates));
+            ui.end_row();
+            ui.label("Out of order");
+            ui.label(format!("{}", health.out_of_order));
+            ui.end_row();
+            ui.label("Counter resets");
+            ui.label(format!("{}", health.resets));
+            ui.end_row();
+            ui.label("Period");
+            ui.label(match health.nominal_period() {
+                Some(period) => format!("{:.3} ms", period * 1e3),
+                None => "-".to_string(),
+            });
+            ui.end_row();
+        });
+        if ui.button("⟲ reset").clicked() {
+            health.reset();
         }
+        ui.separator();
+
+        let jitter: Vec<_> = health
+            .jitter_samples()
+            .iter()
+            .map(|j| nalgebra::Vector3::repeat(j * 1e3))
+            .collect();
+        let (offsets, widths, hist_data) = histogram_data(&jitter, 30);
+
+        egui_plot::Plot::new(window_title)
+            .allow_zoom(true)
+            .allow_drag(true)
+            .allow_scroll(false)
+            .x_axis_label("interval - period [ms]")
+            .y_axis_label("count")
+            .show(ui, |plot_ui| {
+                let bars: Vec<_> = hist_data[0]
+                    .iter()
+                    .enumerate()
+                    .filter(|p| *p.1 > 0)
+                    .map(|p| {
+                        egui_plot::Bar::new(p.0 as f64 * widths[0] + offsets[0], *p.1 as f64)
+                            .vertical()
+                            .width(widths[0])
+                    })
+                    .collect();
+                plot_ui.bar_chart(egui_plot::BarChart::new(bars).name("jitter"));
+            });
     });
 }
 
@@ -0,0 +1,306 @@
+// PX4 ULog reader
+//
+// refs:
+// https://docs.px4.io/main/en/dev_log/ulog_file_format.html
+
+use crate::log_import::{stream_mut, LogSample, LogStream, SensorKind};
+use crate::units::{InputUnits, MagUnit};
+use nalgebra::Vector3;
+use std::collections::HashMap;
+use std::io::{Error, ErrorKind};
+
+const MAGIC: [u8; 7] = [0x55, 0x4c, 0x6f, 0x67, 0x01, 0x12, 0x35];
+const HEADER_LEN: usize = 16;
+
+#[derive(Debug, Clone)]
+struct Field {
+    name: String,
+    type_name: String,
+    offset: usize,
+}
+
+#[derive(Debug, Clone)]
+struct Format {
+    fields: Vec<Field>,
+    size: usize,
+}
+
+impl Format {
+    fn field(&self, name: &str) -> Option<&Field> {
+        self.fields.iter().find(|f| f.name == name)
+    }
+}
+
+#[derive(Debug)]
+struct Subscription {
+    multi_id: u8,
+    message_name: String,
+}
+
+fn primitive_size(type_name: &str) -> Option<usize> {
+    match type_name {
+        "int8_t" | "uint8_t" | "bool" | "char" => Some(1),
+        "int16_t" | "uint16_t" => Some(2),
+        "int32_t" | "uint32_t" | "float" => Some(4),
+        "int64_t" | "uint64_t" | "double" => Some(8),
+        _ => None,
+    }
+}
+
+/// Splits `float[3]` into (`float`, 3).
+fn split_array(type_str: &str) -> (&str, usize) {
+    match type_str.find('[') {
+        Some(i) => {
+            let len = type_str[i + 1..].trim_end_matches(']').parse().unwrap_or(1);
+            (&type_str[..i], len)
+        }
+        None => (type_str, 1),
+    }
+}
+
+/// Resolves the memory layout of all format definitions. Nested types are allowed to be
+/// defined after the types using them, hence the repeated passes.
+fn resolve_formats(definitions: &HashMap<String, String>) -> HashMap<String, Format> {
+    let mut formats: HashMap<String, Format> = HashMap::new();
+
+    loop {
+        let mut progress = false;
+
+        'next: for (name, body) in definitions {
+            if formats.contains_key(name) {
+                continue;
+            }
+
+            let mut fields = vec![];
+            let mut offset = 0;
+
+            for field in body.split(';').filter(|f| !f.is_empty()) {
+                let Some((type_str, field_name)) = field.split_once(' ') else {
+                    continue;
+                };
+                let (type_name, len) = split_array(type_str);
+                let size = match primitive_size(type_name) {
+                    Some(size) => size,
+                    None => match formats.get(type_name) {
+                        Some(nested) => nested.size,
+                        None => continue 'next, // nested type not resolved yet
+                    },
+                };
+
+                fields.push(Field {
+                    name: field_name.to_string(),
+                    type_name: type_name.to_string(),
+                    offset,
+                });
+                offset += size * len;
+            }
+
+            formats.insert(
+                name.clone(),
+                Format {
+                    fields,
+                    size: offset,
+                },
+            );
+            progress = true;
+        }
+
+        if !progress {
+            return formats;
+        }
+    }
+}
+
+fn read_value(data: &[u8], field: &Field, index: usize) -> Option<f64> {
+    let size = primitive_size(&field.type_name)?;
+    let start = field.offset + index * size;
+    let bytes = data.get(start..start + size)?;
+
+    Some(match field.type_name.as_str() {
+        "int8_t" => bytes[0] as i8 as f64,
+        "uint8_t" | "bool" | "char" => bytes[0] as f64,
+        "int16_t" => i16::from_le_bytes(bytes.try_into().ok()?) as f64,
+        "uint16_t" => u16::from_le_bytes(bytes.try_into().ok()?) as f64,
+        "int32_t" => i32::from_le_bytes(bytes.try_into().ok()?) as f64,
+        "uint32_t" => u32::from_le_bytes(bytes.try_into().ok()?) as f64,
+        "int64_t" => i64::from_le_bytes(bytes.try_into().ok()?) as f64,
+        "uint64_t" => u64::from_le_bytes(bytes.try_into().ok()?) as f64,
+        "float" => f32::from_le_bytes(bytes.try_into().ok()?) as f64,
+        "double" => f64::from_le_bytes(bytes.try_into().ok()?),
+        _ => return None,
+    })
+}
+
+/// Reads a vector either from an array field (`gyro_rad[3]`) or from `x`, `y`, `z` fields.
+fn read_vector(data: &[u8], format: &Format, field_name: Option<&str>) -> Option<Vector3<f64>> {
+    match field_name {
+        Some(name) => {
+            let field = format.field(name)?;
+            Some(Vector3::new(
+                read_value(data, field, 0)?,
+                read_value(data, field, 1)?,
+                read_value(data, field, 2)?,
+            ))
+        }
+        None => Some(Vector3::new(
+            read_value(data, format.field("x")?, 0)?,
+            read_value(data, format.field("y")?, 0)?,
+            read_value(data, format.field("z")?, 0)?,
+        )),
+    }
+}
+
+/// Units used by PX4: m/s², rad/s and Gauss
+pub const UNITS: InputUnits = InputUnits::with_mag(MagUnit::Gauss);
+
+/// Sensor topics of interest: (topic, field, kind, stream suffix)
+const TOPICS: [(&str, Option<&str>, SensorKind, &str); 6] = [
+    (
+        "sensor_combined",
+        Some("gyro_rad"),
+        SensorKind::Gyro,
+        " gyro",
+    ),
+    (
+        "sensor_combined",
+        Some("accelerometer_m_s2"),
+        SensorKind::Acc,
+        " accel",
+    ),
+    ("sensor_accel", None, SensorKind::Acc, ""),
+    ("sensor_gyro", None, SensorKind::Gyro, ""),
+    ("sensor_mag", None, SensorKind::Mag, ""),
+    (
+        "vehicle_magnetometer",
+        Some("magnetometer_ga"),
+        SensorKind::Mag,
+        "",
+    ),
+];
+
+pub fn parse(data: &[u8]) -> std::io::Result<Vec<LogStream>> {
+    if data.len() < HEADER_LEN || data[..MAGIC.len()] != MAGIC {
+        return Err(Error::new(ErrorKind::InvalidData, "Not a ULog file"));
+    }
+
+    let mut definitions = HashMap::new();
+    let mut formats = HashMap::new();
+    let mut subscriptions: HashMap<u16, Subscription> = HashMap::new();
+    let mut streams = vec![];
+
+    let mut pos = HEADER_LEN;
+    while pos + 3 <= data.len() {
+        let msg_size = u16::from_le_bytes([data[pos], data[pos + 1]]) as usize;
+        let msg_type = data[pos + 2];
+        let Some(payload) = data.get(pos + 3..pos + 3 + msg_size) else {
+            break; // truncated log
+        };
+        pos += 3 + msg_size;
+
+        match msg_type {
+            b'F' => {
+                let definition = String::from_utf8_lossy(payload);
+                if let Some((name, body)) = definition.split_once(':') {
+                    definitions.insert(name.to_string(), body.to_string());
+                }
+            }
+            b'A' if payload.len() >= 3 => {
+                if formats.is_empty() {
+                    // the definitions section is complete once the first subscription shows up
+                    formats = resolve_formats(&definitions);
+                }
+                subscriptions.insert(
+                    u16::from_le_bytes([payload[1], payload[2]]),
+                    Subscription {
+                        multi_id: payload[0],
+                        message_name: String::from_utf8_lossy(&payload[3..]).to_string(),
+                    },
+                );
+            }
+            b'D' if payload.len() >= 2 => {
+                let msg_id = u16::from_le_bytes([payload[0], payload[1]]);
+                let Some(sub) = subscriptions.get(&msg_id) else {
+                    continue;
+                };
+                let Some(format) = formats.get(&sub.message_name) else {
+                    continue;
+                };
+                let msg = &payload[2..];
+
+                for (topic, field, kind, suffix) in TOPICS {
+                    if sub.message_name != topic {
+                        continue;
+                    }
+
+                    let time = format
+                        .field("timestamp")
+                        .and_then(|f| read_value(msg, f, 0))
+                        .unwrap_or_default()
+                        * 1e-6;
+                    let temperature = format
+                        .field("temperature")
+                        .and_then(|f| read_value(msg, f, 0))
+                        .filter(|t| t.is_finite());
+
+                    if let Some(value) = read_vector(msg, format, field) {
+                        let name = format!("{}[{}]{}", topic, sub.multi_id, suffix);
+                        stream_mut(&mut streams, &name, kind)
+                            .samples
+                            .push(LogSample {
+                                time,
+                                value,
+                                temperature,
+                            });
+                    }
+                }
+            }
+            _ => (),
+        }
+    }
+
+    Ok(streams)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn message(msg_type: u8, payload: &[u8]) -> Vec<u8> {
+        let mut msg = (payload.len() as u16).to_le_bytes().to_vec();
+        msg.push(msg_type);
+        msg.extend_from_slice(payload);
+        msg
+    }
+
+    #[test]
+    fn parse_sensor_mag() {
+        let mut log = MAGIC.to_vec();
+        log.extend_from_slice(&[0; HEADER_LEN - MAGIC.len()]);
+        log.extend(message(
+            b'F',
+            b"sensor_mag:uint64_t timestamp;uint32_t device_id;float x;float y;float z",
+        ));
+
+        let mut add = vec![1u8];
+        add.extend_from_slice(&7u16.to_le_bytes());
+        add.extend_from_slice(b"sensor_mag");
+        log.extend(message(b'A', &add));
+
+        let mut data = 7u16.to_le_bytes().to_vec();
+        data.extend_from_slice(&2_000_000u64.to_le_bytes());
+        data.extend_from_slice(&42u32.to_le_bytes());
+        for v in [0.2f32, -0.1, 0.45] {
+            data.extend_from_slice(&v.to_le_bytes());
+        }
+        log.extend(message(b'D', &data));
+
+        let streams = parse(&log).unwrap();
+        assert_eq!(streams.len(), 1);
+        assert_eq!(streams[0].name, "sensor_mag[1]");
+        assert_eq!(streams[0].kind, SensorKind::Mag);
+
+        let sample = streams[0].samples[0];
+        assert!((sample.time - 2.0).abs() < 1e-9);
+        assert!((sample.value - Vector3::new(0.2, -0.1, 0.45)).norm() < 1e-6);
+    }
+}
@@ -0,0 +1,239 @@
+use crate::data_provider::{ImuData, MagData};
+
+const G0: f64 = 9.80665; // standard gravity, used by the g and mg units only
+
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
+pub enum AccUnit {
+    MeterPerSecond2,
+    G,
+    MilliG,
+}
+
+impl AccUnit {
+    pub const ALL: [AccUnit; 3] = [AccUnit::MeterPerSecond2, AccUnit::G, AccUnit::MilliG];
+
+    /// Factor to m/s²
+    pub fn factor(&self) -> f64 {
+        match self {
+            AccUnit::MeterPerSecond2 => 1.0,
+            AccUnit::G => G0,
+            AccUnit::MilliG => G0 * 1e-3,
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            AccUnit::MeterPerSecond2 => "m/s²",
+            AccUnit::G => "g",
+            AccUnit::MilliG => "mg",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
+pub enum GyroUnit {
+    RadPerSecond,
+    DegPerSecond,
+}
+
+impl GyroUnit {
+    pub const ALL: [GyroUnit; 2] = [GyroUnit::RadPerSecond, GyroUnit::DegPerSecond];
+
+    /// Factor to rad/s
+    pub fn factor(&self) -> f64 {
+        match self {
+            GyroUnit::RadPerSecond => 1.0,
+            GyroUnit::DegPerSecond => 1f64.to_radians(),
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            GyroUnit::RadPerSecond => "rad/s",
+            GyroUnit::DegPerSecond => "°/s",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
+pub enum MagUnit {
+    MicroTesla,
+    Tesla,
+    NanoTesla,
+    Gauss,
+    MilliGauss,
+    Arbitrary, // e.g. normalized to the local field strength, passed through as is
+}
+
+impl MagUnit {
+    pub const ALL: [MagUnit; 6] = [
+        MagUnit::MicroTesla,
+        MagUnit::Tesla,
+        MagUnit::NanoTesla,
+        MagUnit::Gauss,
+        MagUnit::MilliGauss,
+        MagUnit::Arbitrary,
+    ];
+
+    /// Factor to µT
+    pub fn factor(&self) -> f64 {
+        match self {
+            MagUnit::MicroTesla => 1.0,
+            MagUnit::Tesla => 1e6,
+            MagUnit::NanoTesla => 1e-3,
+            MagUnit::Gauss => 100.0,
+            MagUnit::MilliGauss => 0.1,
+            MagUnit::Arbitrary => 1.0,
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            MagUnit::MicroTesla => "µT",
+            MagUnit::Tesla => "T",
+            MagUnit::NanoTesla => "nT",
+            MagUnit::Gauss => "G",
+            MagUnit::MilliGauss => "mG",
+            MagUnit::Arbitrary => "a.u.",
+        }
+    }
+}
+
+/// Units of the values a provider sends. Raw counts are first multiplied by the LSB scale
+/// (e.g. 1/16384 for a ±2 g accelerometer) to get to the unit. Internally everything is
+/// in m/s², rad/s and µT.
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct InputUnits {
+    pub acc: AccUnit,
+    pub acc_lsb: f64,
+    pub gyro: GyroUnit,
+    pub gyro_lsb: f64,
+    pub mag: MagUnit,
+    pub mag_lsb: f64,
+}
+
+impl Default for InputUnits {
+    fn default() -> Self {
+        Self::with_mag(MagUnit::MicroTesla)
+    }
+}
+
+impl InputUnits {
+    /// SI accel and gyro units, the magnetometer unit varies too much to have a default.
+    pub const fn with_mag(mag: MagUnit) -> Self {
+        Self {
+            acc: AccUnit::MeterPerSecond2,
+            acc_lsb: 1.0,
+            gyro: GyroUnit::RadPerSecond,
+            gyro_lsb: 1.0,
+            mag,
+            mag_lsb: 1.0,
+        }
+    }
+
+    pub fn acc_factor(&self) -> f64 {
+        self.acc_lsb * self.acc.factor()
+    }
+
+    pub fn gyro_factor(&self) -> f64 {
+        self.gyro_lsb * self.gyro.factor()
+    }
+
+    pub fn mag_factor(&self) -> f64 {
+        self.mag_lsb * self.mag.factor()
+    }
+
+    pub fn normalize_imu(&self, data: ImuData) -> ImuData {
+        ImuData {
+            lin_acc: data.lin_acc * self.acc_factor(),
+            ang_vel: data.ang_vel * self.gyro_factor(),
+            ..data
+        }
+    }
+
+    pub fn normalize_mag(&self, data: MagData) -> MagData {
+        MagData {
+            field: data.field * self.mag_factor(),
+            ..data
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use nalgebra::Vector3;
+
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() <= 1e-12 * b.abs().max(1.0)
+    }
+
+    #[test]
+    fn acc_units() {
+        let mut units = InputUnits::default();
+        assert!(close(units.acc_factor(), 1.0));
+        units.acc = AccUnit::G;
+        assert!(close(units.acc_factor(), 9.80665));
+        units.acc = AccUnit::MilliG;
+        assert!(close(units.acc_factor(), 9.80665e-3));
+        // ±2 g accelerometer, 16384 LSB/g
+        units.acc = AccUnit::G;
+        units.acc_lsb = 1.0 / 16384.0;
+        assert!(close(16384.0 * units.acc_factor(), 9.80665));
+    }
+
+    #[test]
+    fn gyro_units() {
+        let mut units = InputUnits::default();
+        assert!(close(units.gyro_factor(), 1.0));
+        units.gyro = GyroUnit::DegPerSecond;
+        assert!(close(180.0 * units.gyro_factor(), std::f64::consts::PI));
+        // ±2000 °/s gyro, 16.384 LSB/(°/s)
+        units.gyro_lsb = 1.0 / 16.384;
+        assert!(close(
+            16.384 * 90.0 * units.gyro_factor(),
+            std::f64::consts::FRAC_PI_2
+        ));
+    }
+
+    #[test]
+    fn mag_units() {
+        for (unit, micro_tesla) in [
+            (MagUnit::MicroTesla, 1.0),
+            (MagUnit::Tesla, 1e6),
+            (MagUnit::NanoTesla, 1e-3),
+            (MagUnit::Gauss, 100.0),
+            (MagUnit::MilliGauss, 0.1),
+            (MagUnit::Arbitrary, 1.0),
+        ] {
+            assert!(close(InputUnits::with_mag(unit).mag_factor(), micro_tesla));
+        }
+        // HMC5883L at ±1.3 Ga, 1090 LSB/Ga
+        let mut units = InputUnits::with_mag(MagUnit::Gauss);
+        units.mag_lsb = 1.0 / 1090.0;
+        assert!(close(1090.0 * units.mag_factor(), 100.0));
+    }
+
+    #[test]
+    fn normalize() {
+        let mut units = InputUnits::with_mag(MagUnit::MilliGauss);
+        units.acc = AccUnit::G;
+        units.gyro = GyroUnit::DegPerSecond;
+        let imu = units.normalize_imu(ImuData {
+            lin_acc: Vector3::new(0.0, 0.0, 1.0),
+            ang_vel: Vector3::new(90.0, 0.0, -180.0),
+            info: Default::default(),
+            device: String::new(),
+        });
+        assert!((imu.lin_acc - Vector3::new(0.0, 0.0, 9.80665)).norm() < 1e-12);
+        let expected = Vector3::new(std::f64::consts::FRAC_PI_2, 0.0, -std::f64::consts::PI);
+        assert!((imu.ang_vel - expected).norm() < 1e-12);
+
+        let mag = units.normalize_mag(MagData {
+            field: Vector3::new(500.0, -200.0, 0.0),
+            info: Default::default(),
+            device: String::new(),
+        });
+        assert!((mag.field - Vector3::new(50.0, -20.0, 0.0)).norm() < 1e-12);
+    }
+}
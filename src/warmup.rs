@@ -0,0 +1,190 @@
+// Gyro bias warm-up
+//
+// The gyro bias drifts after power-on until the sensor reaches its operating temperature.
+// The recording is averaged over short windows and a settling curve is fitted per axis:
+//
+//   b(t) = b_final + a * exp(-t / tau)
+//
+// For a fixed tau the model is linear in b_final and a, so tau is found by a search over
+// log-spaced values and the other two by least squares.
+
+use crate::data_provider::SampleInfo;
+use crate::gyro_cal;
+use nalgebra::{Matrix2, Vector2, Vector3};
+
+const TAU_STEPS: usize = 100;
+const GOLDEN_STEPS: usize = 40;
+const MIN_WINDOWS: usize = 5;
+
+#[derive(Debug, Clone, Copy)]
+pub struct WarmupFit {
+    pub bias_final: Vector3<f64>,    // rad/s
+    pub amplitude: Vector3<f64>,     // rad/s, at t = 0
+    pub time_constant: Vector3<f64>, // s
+}
+
+impl WarmupFit {
+    pub fn bias_at(&self, t: f64) -> Vector3<f64> {
+        self.bias_final
+            + self
+                .amplitude
+                .zip_map(&self.time_constant, |a, tau| a * (-t / tau).exp())
+    }
+
+    /// Time after which the bias stays within `tolerance` of its final value.
+    pub fn settle_time(&self, tolerance: f64) -> f64 {
+        (0..3)
+            .map(|i| {
+                let a = self.amplitude[i].abs();
+                if a > tolerance {
+                    self.time_constant[i] * (a / tolerance).ln()
+                } else {
+                    0.0
+                }
+            })
+            .fold(0.0, f64::max)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Warmup {
+    pub rolling: Vec<(f64, Vector3<f64>)>, // s since the first sample, mean of the window
+    pub fit: WarmupFit,
+}
+
+/// Means over consecutive windows of `window` seconds.
+pub fn rolling_bias(
+    points: &[Vector3<f64>],
+    times: &[f64],
+    window: f64,
+) -> Vec<(f64, Vector3<f64>)> {
+    let Some(&start) = times.first() else {
+        return vec![];
+    };
+
+    let mut rolling = vec![];
+    let mut sum = Vector3::zeros();
+    let mut time_sum = 0.0;
+    let mut count = 0;
+    let mut end = start + window;
+    for (p, t) in points.iter().zip(times) {
+        if *t >= end && count > 0 {
+            rolling.push((time_sum / count as f64, sum / count as f64));
+            sum = Vector3::zeros();
+            time_sum = 0.0;
+            count = 0;
+            while *t >= end {
+                end += window;
+            }
+        }
+        sum += p;
+        time_sum += t - start;
+        count += 1;
+    }
+    if count > 0 {
+        rolling.push((time_sum / count as f64, sum / count as f64));
+    }
+    rolling
+}
+
+/// Least squares b_final and a for a given tau, returns them with the squared error.
+fn fit_linear(t: &[f64], y: &[f64], tau: f64) -> Option<(f64, f64, f64)> {
+    let mut ata = Matrix2::zeros();
+    let mut aty = Vector2::zeros();
+    for (t, y) in t.iter().zip(y) {
+        let row = Vector2::new(1.0, (-t / tau).exp());
+        ata += row * row.transpose();
+        aty += row * *y;
+    }
+    let c = ata.try_inverse()? * aty;
+    let error = t
+        .iter()
+        .zip(y)
+        .map(|(t, y)| (c[0] + c[1] * (-t / tau).exp() - y).powi(2))
+        .sum();
+    Some((c[0], c[1], error))
+}
+
+/// Fits b_final + a * exp(-t / tau), tau between a tenth of the first window and ten
+/// times the recording. The best tau of the grid is refined by a golden section search
+/// between its neighbours.
+pub fn fit_exponential(t: &[f64], y: &[f64]) -> Option<(f64, f64, f64)> {
+    let log_min = (t.first()?.max(1e-3) / 10.0).ln();
+    let log_max = (t.last()? * 10.0).ln();
+    let step = (log_max - log_min) / (TAU_STEPS - 1) as f64;
+    let error = |log_tau: f64| fit_linear(t, y, log_tau.exp()).map_or(f64::INFINITY, |f| f.2);
+
+    let best = (0..TAU_STEPS)
+        .map(|i| log_min + i as f64 * step)
+        .min_by(|a, b| error(*a).partial_cmp(&error(*b)).unwrap())?;
+
+    let ratio = (5f64.sqrt() - 1.0) / 2.0;
+    let (mut lo, mut hi) = (best - step, best + step);
+    for _ in 0..GOLDEN_STEPS {
+        let a = hi - ratio * (hi - lo);
+        let b = lo + ratio * (hi - lo);
+        if error(a) < error(b) {
+            hi = b;
+        } else {
+            lo = a;
+        }
+    }
+
+    let tau = ((lo + hi) / 2.0).exp();
+    let (b, a, _) = fit_linear(t, y, tau)?;
+    Some((b, a, tau))
+}
+
+/// Rolling bias and settling curve of a gyro recording started at power-on. The sensor
+/// must not move.
+pub fn analyze(points: &[Vector3<f64>], info: &[SampleInfo], window: f64) -> Option<Warmup> {
+    let times = gyro_cal::sample_times(info)?;
+    let rolling = rolling_bias(points, &times, window);
+    if rolling.len() < MIN_WINDOWS {
+        return None;
+    }
+
+    let t: Vec<_> = rolling.iter().map(|(t, _)| *t).collect();
+    let mut fit = WarmupFit {
+        bias_final: Vector3::zeros(),
+        amplitude: Vector3::zeros(),
+        time_constant: Vector3::zeros(),
+    };
+    for axis in 0..3 {
+        let y: Vec<_> = rolling.iter().map(|(_, b)| b[axis]).collect();
+        let (b, a, tau) = fit_exponential(&t, &y)?;
+        fit.bias_final[axis] = b;
+        fit.amplitude[axis] = a;
+        fit.time_constant[axis] = tau;
+    }
+
+    Some(Warmup { rolling, fit })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn settling() {
+        let bias_final = Vector3::new(0.01, -0.005, 0.002);
+        let amplitude = Vector3::new(0.02, 0.01, -0.004);
+        let mut points = vec![];
+        let mut info = vec![];
+        for i in 0..60_000 {
+            let t = i as f64 * 0.01; // 10 min at 100 Hz
+            let noise = if i % 2 == 0 { 1e-3 } else { -1e-3 };
+            points.push(bias_final + amplitude * (-t / 60.0).exp() + Vector3::repeat(noise));
+            info.push(SampleInfo {
+                device_time: Some(t),
+                ..Default::default()
+            });
+        }
+
+        let warmup = analyze(&points, &info, 5.0).unwrap();
+        assert!((warmup.fit.bias_final - bias_final).norm() < 1e-5);
+        assert!((warmup.fit.time_constant.x / 60.0 - 1.0).abs() < 0.05);
+        // 60 s * ln(0.02 / 1e-4)
+        assert!((warmup.fit.settle_time(1e-4) - 318.0).abs() < 10.0);
+    }
+}
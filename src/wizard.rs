@@ -0,0 +1,142 @@
+// Guided six-face accelerometer calibration
+//
+// Asks for each face to point up in turn, waits until the device rests in that
+// orientation and collects for a fixed time before moving on.
+
+use crate::axis_map::Axis;
+use nalgebra::Vector3;
+
+const G0: f64 = 9.80665;
+const MAX_TILT: f64 = 20.0; // °, between the face and the gravity vector
+const STILL_TOLERANCE: f64 = 0.2; // m/s², deviation from the moving average
+const DEFAULT_PERIOD: f64 = 0.01; // s, assumed if the samples are not timestamped
+
+pub const FACES: [Axis; 6] = Axis::ALL;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WizardState {
+    WrongOrientation(Option<Axis>), // the face currently pointing up, if any
+    Moving,
+    Collecting,
+    Done,
+}
+
+#[derive(Debug)]
+pub struct SixFaceWizard {
+    pub step: usize,    // index into FACES
+    pub duration: f64,  // s to collect per face
+    pub collected: f64, // s collected of the current face
+    pub skipped: Vec<Axis>,
+    avg: Option<Vector3<f64>>,
+    last_time: Option<f64>,
+    state: WizardState,
+}
+
+impl SixFaceWizard {
+    pub fn new(duration: f64) -> Self {
+        Self {
+            step: 0,
+            duration,
+            collected: 0.0,
+            skipped: vec![],
+            avg: None,
+            last_time: None,
+            state: WizardState::Moving,
+        }
+    }
+
+    pub fn face(&self) -> Option<Axis> {
+        FACES.get(self.step).copied()
+    }
+
+    pub fn state(&self) -> WizardState {
+        self.state
+    }
+
+    pub fn progress(&self) -> f32 {
+        (self.collected / self.duration).min(1.0) as f32
+    }
+
+    pub fn skip(&mut self) {
+        if let Some(face) = self.face() {
+            self.skipped.push(face);
+            self.next_face();
+        }
+    }
+
+    fn next_face(&mut self) {
+        self.step += 1;
+        self.collected = 0.0;
+        if self.face().is_none() {
+            self.state = WizardState::Done;
+        }
+    }
+
+    /// Feeds an accel sample (m/s²), returns true if it belongs to the current face and
+    /// should be collected.
+    pub fn update(&mut self, acc: Vector3<f64>, time: Option<f64>) -> bool {
+        let Some(face) = self.face() else {
+            return false;
+        };
+
+        let dt = match (time, self.last_time) {
+            (Some(time), Some(last)) if time > last => (time - last).min(0.5),
+            (Some(_), Some(_)) => 0.0,
+            _ => DEFAULT_PERIOD,
+        };
+        self.last_time = time;
+
+        let avg = self.avg.map_or(acc, |avg| avg * 0.9 + acc * 0.1);
+        self.avg = Some(avg);
+
+        let up = FACES
+            .into_iter()
+            .find(|f| acc.angle(&f.unit_vector()).to_degrees() < MAX_TILT);
+        if up != Some(face) {
+            self.state = WizardState::WrongOrientation(up);
+            return false;
+        }
+        if (acc - avg).norm() > STILL_TOLERANCE || (acc.norm() - G0).abs() > 0.1 * G0 {
+            self.state = WizardState::Moving;
+            return false;
+        }
+
+        self.state = WizardState::Collecting;
+        self.collected += dt;
+        if self.collected >= self.duration {
+            self.next_face();
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn walks_through_faces() {
+        let mut wizard = SixFaceWizard::new(1.0);
+        let mut time = 0.0;
+        let mut collected = 0;
+
+        // wrong face first
+        assert!(!wizard.update(Vector3::new(0.0, 0.0, G0), Some(time)));
+        assert_eq!(
+            wizard.state(),
+            WizardState::WrongOrientation(Some(Axis::PosZ))
+        );
+
+        for face in FACES {
+            for _ in 0..200 {
+                time += 0.01;
+                if wizard.update(face.unit_vector() * G0, Some(time)) {
+                    collected += 1;
+                }
+            }
+        }
+
+        assert_eq!(wizard.state(), WizardState::Done);
+        assert!(collected >= 6 * 100);
+    }
+}
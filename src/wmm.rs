@@ -0,0 +1,398 @@
+// World Magnetic Model
+//
+// Spherical harmonic model of the main geomagnetic field up to degree 12, with a linear
+// secular variation over its five year validity. The coefficients are embedded in the
+// format of NOAA's WMM.COF, other coefficient files (e.g. a newer release) can be parsed
+// with `Wmm::parse`.
+//
+// refs:
+// https://www.ncei.noaa.gov/products/world-magnetic-model
+// A. Chulliat et al., "The US/UK World Magnetic Model for 2025-2030: Technical Report"
+
+use nalgebra::Vector3;
+use std::sync::OnceLock;
+
+const MAX_DEGREE: usize = 12;
+const REFERENCE_RADIUS: f64 = 6371.2; // km
+const WGS84_A: f64 = 6378.137; // km
+const WGS84_E2: f64 = 0.00669437999013;
+
+const WMM2025_COF: &str = "
+    2025.0            WMM-2025     11/13/2024
+  1  0  -29351.8       0.0       12.0        0.0
+  1  1   -1410.8    4545.4        9.7      -21.5
+  2  0   -2556.6       0.0      -11.6        0.0
+  2  1    2951.1   -3133.6       -5.2      -27.7
+  2  2    1649.3    -815.1       -8.0      -12.1
+  3  0    1361.0       0.0       -1.3        0.0
+  3  1   -2404.1     -56.6       -4.2        4.0
+  3  2    1243.8     237.5        0.4       -0.3
+  3  3     453.6    -549.5      -15.6       -4.1
+  4  0     895.0       0.0       -1.6        0.0
+  4  1     799.5     278.6       -2.4       -1.1
+  4  2      55.7    -133.9       -6.0        4.1
+  4  3    -281.1     212.0        5.6        1.6
+  4  4      12.1    -375.6       -7.0       -4.4
+  5  0    -233.2       0.0        0.6        0.0
+  5  1     368.9      45.4        1.4       -0.5
+  5  2     187.2     220.2        0.0        2.2
+  5  3    -138.7    -122.9        0.6        0.4
+  5  4    -142.0      43.0        2.2        1.7
+  5  5      20.9     106.1        0.9        1.9
+  6  0      64.4       0.0       -0.2        0.0
+  6  1      63.8     -18.4       -0.4        0.3
+  6  2      76.9      16.8        0.9       -1.6
+  6  3    -115.7      48.8        1.2       -0.4
+  6  4     -40.9     -59.8       -0.9        0.9
+  6  5      14.9      10.9        0.3        0.7
+  6  6     -60.7      72.7        0.9        0.9
+  7  0      79.5       0.0       -0.0        0.0
+  7  1     -77.0     -48.9       -0.1        0.6
+  7  2      -8.8     -14.4       -0.1        0.5
+  7  3      59.3      -1.0        0.5       -0.8
+  7  4      15.8      23.4       -0.1        0.0
+  7  5       2.5      -7.4       -0.8       -1.0
+  7  6     -11.1     -25.1       -0.8        0.6
+  7  7      14.2      -2.3        0.8       -0.2
+  8  0      23.2       0.0       -0.1        0.0
+  8  1      10.8       7.1        0.2       -0.2
+  8  2     -17.5     -12.6        0.0        0.5
+  8  3       2.0      11.4        0.5       -0.4
+  8  4     -21.7      -9.7       -0.1        0.4
+  8  5      16.9      12.7        0.3       -0.5
+  8  6      15.0       0.7        0.2       -0.6
+  8  7     -16.8      -5.2       -0.0        0.3
+  8  8       0.9       3.9        0.2        0.2
+  9  0       4.6       0.0       -0.0        0.0
+  9  1       7.8     -24.8       -0.1       -0.3
+  9  2       3.0      12.2        0.1        0.3
+  9  3      -0.2       8.3        0.3       -0.3
+  9  4      -2.5      -3.3       -0.3        0.3
+  9  5     -13.1      -5.2        0.0        0.2
+  9  6       2.4       7.2        0.3       -0.1
+  9  7       8.6      -0.6       -0.1       -0.2
+  9  8      -8.7       0.8        0.1        0.4
+  9  9     -12.9      10.0       -0.1        0.1
+ 10  0      -1.3       0.0        0.1        0.0
+ 10  1      -6.4       3.3        0.0        0.0
+ 10  2       0.2       0.0        0.1       -0.0
+ 10  3       2.0       2.4        0.1       -0.2
+ 10  4      -1.0       5.3       -0.0        0.1
+ 10  5      -0.6      -9.1       -0.3       -0.1
+ 10  6      -0.9       0.4        0.0        0.1
+ 10  7       1.5      -4.2       -0.1        0.0
+ 10  8       0.9      -3.8       -0.1       -0.1
+ 10  9      -2.7       0.9       -0.0        0.2
+ 10 10      -3.9      -9.1       -0.0       -0.0
+ 11  0       2.9       0.0        0.0        0.0
+ 11  1      -1.5       0.0       -0.0       -0.0
+ 11  2      -2.5       2.9        0.0        0.1
+ 11  3       2.4      -0.6        0.0       -0.0
+ 11  4      -0.6       0.2        0.0        0.1
+ 11  5      -0.1       0.5       -0.1       -0.0
+ 11  6      -0.6      -0.3        0.0       -0.0
+ 11  7      -0.1      -1.2       -0.0        0.1
+ 11  8       1.1      -1.7       -0.1       -0.0
+ 11  9      -1.0      -2.9       -0.1        0.0
+ 11 10      -0.2      -1.8       -0.1        0.0
+ 11 11       2.6      -2.3       -0.1        0.0
+ 12  0      -2.0       0.0        0.0        0.0
+ 12  1      -0.2      -1.3        0.0       -0.0
+ 12  2       0.3       0.7       -0.0        0.0
+ 12  3       1.2       1.0       -0.0       -0.1
+ 12  4      -1.3      -1.4       -0.0        0.1
+ 12  5       0.6      -0.0       -0.0       -0.0
+ 12  6       0.6       0.6        0.1       -0.0
+ 12  7       0.5      -0.1       -0.0       -0.0
+ 12  8      -0.1       0.8        0.0        0.0
+ 12  9      -0.4       0.1        0.0       -0.0
+ 12 10      -0.2      -1.0       -0.1       -0.0
+ 12 11      -1.3       0.1       -0.0        0.0
+ 12 12      -0.7       0.2       -0.1       -0.1
+999999999999999999999999999999999999999999999999
+999999999999999999999999999999999999999999999999
+";
+
+/// Field vector in the local NED frame.
+#[derive(Debug, Clone, Copy)]
+pub struct MagField {
+    pub ned: Vector3<f64>, // µT
+}
+
+impl MagField {
+    /// Total intensity in µT.
+    pub fn intensity(&self) -> f64 {
+        self.ned.norm()
+    }
+
+    /// Inclination (dip) in rad, positive down.
+    pub fn inclination(&self) -> f64 {
+        self.ned.z.atan2(self.ned.xy().norm())
+    }
+
+    /// Declination in rad, positive east.
+    pub fn declination(&self) -> f64 {
+        self.ned.y.atan2(self.ned.x)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Wmm {
+    pub epoch: f64, // decimal year
+    // Gauss coefficients and their secular variation in nT and nT/year, [n][m]
+    g: [[f64; MAX_DEGREE + 1]; MAX_DEGREE + 1],
+    h: [[f64; MAX_DEGREE + 1]; MAX_DEGREE + 1],
+    g_dot: [[f64; MAX_DEGREE + 1]; MAX_DEGREE + 1],
+    h_dot: [[f64; MAX_DEGREE + 1]; MAX_DEGREE + 1],
+}
+
+impl Wmm {
+    /// The embedded WMM2025, valid 2025.0 to 2030.0.
+    pub fn wmm2025() -> &'static Self {
+        static WMM2025: OnceLock<Wmm> = OnceLock::new();
+        WMM2025.get_or_init(|| Self::parse(WMM2025_COF).unwrap())
+    }
+
+    /// Parses a coefficient file in the WMM.COF format.
+    pub fn parse(cof: &str) -> Option<Self> {
+        let mut lines = cof.lines().filter(|l| !l.trim().is_empty());
+        let epoch = lines.next()?.split_whitespace().next()?.parse().ok()?;
+
+        let zero = [[0.0; MAX_DEGREE + 1]; MAX_DEGREE + 1];
+        let mut wmm = Wmm {
+            epoch,
+            g: zero,
+            h: zero,
+            g_dot: zero,
+            h_dot: zero,
+        };
+        for line in lines {
+            if line.starts_with("9999") {
+                break;
+            }
+            let values: Vec<f64> = line
+                .split_whitespace()
+                .map(|v| v.parse().ok())
+                .collect::<Option<_>>()?;
+            let &[n, m, g, h, g_dot, h_dot] = values.as_slice() else {
+                return None;
+            };
+            let (n, m) = (n as usize, m as usize);
+            if n > MAX_DEGREE || m > n {
+                return None;
+            }
+            wmm.g[n][m] = g;
+            wmm.h[n][m] = h;
+            wmm.g_dot[n][m] = g_dot;
+            wmm.h_dot[n][m] = h_dot;
+        }
+        Some(wmm)
+    }
+
+    /// Field at the geodetic latitude and longitude (rad), height above the WGS84
+    /// ellipsoid (m) and decimal year.
+    pub fn field(&self, latitude: f64, longitude: f64, height: f64, year: f64) -> MagField {
+        // geodetic to geocentric spherical
+        let h = height * 1e-3;
+        let (sin_lat, cos_lat) = latitude.sin_cos();
+        let rc = WGS84_A / (1.0 - WGS84_E2 * sin_lat * sin_lat).sqrt();
+        let p = (rc + h) * cos_lat;
+        let z = (rc * (1.0 - WGS84_E2) + h) * sin_lat;
+        let r = p.hypot(z);
+        let lat_c = (z / r).asin();
+
+        // Gauss normalized Legendre functions of the colatitude and their derivatives,
+        // converted to Schmidt semi-normalized by the factors `s`
+        let (sin_t, cos_t) = (lat_c.cos(), lat_c.sin());
+        let mut p_nm = [[0.0; MAX_DEGREE + 1]; MAX_DEGREE + 1];
+        let mut dp_nm = [[0.0; MAX_DEGREE + 1]; MAX_DEGREE + 1];
+        let mut s = [[0.0; MAX_DEGREE + 1]; MAX_DEGREE + 1];
+        p_nm[0][0] = 1.0;
+        s[0][0] = 1.0;
+        for n in 1..=MAX_DEGREE {
+            for m in 0..=n {
+                if n == m {
+                    p_nm[n][n] = sin_t * p_nm[n - 1][n - 1];
+                    dp_nm[n][n] = sin_t * dp_nm[n - 1][n - 1] + cos_t * p_nm[n - 1][n - 1];
+                } else {
+                    let k = if n > 1 {
+                        ((n - 1) * (n - 1) - m * m) as f64 / ((2 * n - 1) * (2 * n - 3)) as f64
+                    } else {
+                        0.0
+                    };
+                    let (p2, dp2) = if n > 1 {
+                        (p_nm[n - 2][m], dp_nm[n - 2][m])
+                    } else {
+                        (0.0, 0.0)
+                    };
+                    p_nm[n][m] = cos_t * p_nm[n - 1][m] - k * p2;
+                    dp_nm[n][m] = cos_t * dp_nm[n - 1][m] - sin_t * p_nm[n - 1][m] - k * dp2;
+                }
+                s[n][m] = if m == 0 {
+                    s[n - 1][0] * (2 * n - 1) as f64 / n as f64
+                } else {
+                    let delta = if m == 1 { 2.0 } else { 1.0 };
+                    s[n][m - 1] * ((n - m + 1) as f64 * delta / (n + m) as f64).sqrt()
+                };
+            }
+        }
+
+        let dt = year - self.epoch;
+        let (mut b_r, mut b_t, mut b_l) = (0.0, 0.0, 0.0);
+        for n in 1..=MAX_DEGREE {
+            let ratio = (REFERENCE_RADIUS / r).powi(n as i32 + 2);
+            for m in 0..=n {
+                let g = (self.g[n][m] + dt * self.g_dot[n][m]) * s[n][m];
+                let h = (self.h[n][m] + dt * self.h_dot[n][m]) * s[n][m];
+                let (sin_ml, cos_ml) = (m as f64 * longitude).sin_cos();
+                let a = g * cos_ml + h * sin_ml;
+                b_r += (n + 1) as f64 * ratio * a * p_nm[n][m];
+                b_t -= ratio * a * dp_nm[n][m];
+                b_l -= ratio * m as f64 * (-g * sin_ml + h * cos_ml) * p_nm[n][m];
+            }
+        }
+        b_l /= sin_t.max(1e-10);
+
+        // spherical to geodetic NED
+        let (x_c, y_c, z_c) = (-b_t, b_l, -b_r);
+        let psi = lat_c - latitude;
+        let north = x_c * psi.cos() - z_c * psi.sin();
+        let down = x_c * psi.sin() + z_c * psi.cos();
+        MagField {
+            ned: Vector3::new(north, y_c, down) * 1e-3, // nT to µT
+        }
+    }
+}
+
+/// Current date as decimal year.
+pub fn current_year() -> f64 {
+    let secs = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0.0, |d| d.as_secs_f64());
+    1970.0 + secs / (365.2425 * 86400.0)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn factorial(n: usize) -> f64 {
+        (1..=n).map(|k| k as f64).product()
+    }
+
+    /// Schmidt semi-normalized associated Legendre function from the explicit sum.
+    fn legendre(n: usize, m: usize, x: f64) -> f64 {
+        let sum: f64 = (0..=(n - m) / 2)
+            .map(|k| {
+                let sign = if k % 2 == 0 { 1.0 } else { -1.0 };
+                sign * factorial(2 * n - 2 * k)
+                    / (2f64.powi(n as i32)
+                        * factorial(k)
+                        * factorial(n - k)
+                        * factorial(n - m - 2 * k))
+                    * x.powi((n - m - 2 * k) as i32)
+            })
+            .sum();
+        let norm = if m == 0 {
+            1.0
+        } else {
+            (2.0 * factorial(n - m) / factorial(n + m)).sqrt()
+        };
+        norm * (1.0 - x * x).powf(m as f64 / 2.0) * sum
+    }
+
+    /// Reference field in NED (nT): minus the gradient of the potential, by central
+    /// differences in ECEF, projected onto the geodetic north, east and down directions.
+    fn reference_field(wmm: &Wmm, lat: f64, lon: f64, height_km: f64, year: f64) -> Vector3<f64> {
+        let dt = year - wmm.epoch;
+        let potential = |p: Vector3<f64>| {
+            let r = p.norm();
+            let x = p.z / r; // cos of the colatitude
+            let lambda = p.y.atan2(p.x);
+            let mut v = 0.0;
+            for n in 1..=MAX_DEGREE {
+                for m in 0..=n {
+                    let g = wmm.g[n][m] + dt * wmm.g_dot[n][m];
+                    let h = wmm.h[n][m] + dt * wmm.h_dot[n][m];
+                    v += (REFERENCE_RADIUS / r).powi(n as i32 + 1)
+                        * (g * (m as f64 * lambda).cos() + h * (m as f64 * lambda).sin())
+                        * legendre(n, m, x);
+                }
+            }
+            REFERENCE_RADIUS * v
+        };
+
+        let (sin_lat, cos_lat) = lat.sin_cos();
+        let (sin_lon, cos_lon) = lon.sin_cos();
+        let n = WGS84_A / (1.0 - WGS84_E2 * sin_lat * sin_lat).sqrt();
+        let pos = Vector3::new(
+            (n + height_km) * cos_lat * cos_lon,
+            (n + height_km) * cos_lat * sin_lon,
+            (n * (1.0 - WGS84_E2) + height_km) * sin_lat,
+        );
+
+        let step = 1e-3; // km
+        let b = -Vector3::from_fn(|i, _| {
+            let mut d = Vector3::zeros();
+            d[i] = step;
+            (potential(pos + d) - potential(pos - d)) / (2.0 * step)
+        });
+
+        let north = Vector3::new(-sin_lat * cos_lon, -sin_lat * sin_lon, cos_lat);
+        let east = Vector3::new(-sin_lon, cos_lon, 0.0);
+        let down = Vector3::new(-cos_lat * cos_lon, -cos_lat * sin_lon, -sin_lat);
+        Vector3::new(b.dot(&north), b.dot(&east), b.dot(&down))
+    }
+
+    #[test]
+    fn synthesis() {
+        // the points of the WMM test values: poles, equator, 0 and 100 km, epoch and
+        // mid-term
+        let wmm = Wmm::wmm2025();
+        for (lat, lon, height_km, year) in [
+            (80.0f64, 0.0f64, 0.0, 2025.0),
+            (0.0, 120.0, 0.0, 2025.0),
+            (-80.0, 240.0, 0.0, 2025.0),
+            (80.0, 0.0, 100.0, 2027.5),
+            (0.0, 120.0, 100.0, 2027.5),
+            (-80.0, 240.0, 100.0, 2027.5),
+            (40.0, -105.25, 1.65, 2029.9),
+        ] {
+            let (lat, lon) = (lat.to_radians(), lon.to_radians());
+            let field = wmm.field(lat, lon, height_km * 1e3, year);
+            let expected = MagField {
+                ned: reference_field(wmm, lat, lon, height_km, year) * 1e-3,
+            };
+
+            // 1 nT, 0.01°
+            assert!(
+                (field.ned - expected.ned).norm() < 1e-3,
+                "{} {}",
+                field.ned,
+                expected.ned
+            );
+            assert!((field.declination() - expected.declination()).abs() < 0.01f64.to_radians());
+            assert!((field.inclination() - expected.inclination()).abs() < 0.01f64.to_radians());
+        }
+    }
+
+    #[test]
+    fn boulder() {
+        // Boulder, CO, mid 2025: D ~ 7.7° E, I ~ 65.5°, F ~ 51.7 µT, a check of the
+        // embedded coefficients, the synthesis is tested above
+        let wmm = Wmm::wmm2025();
+        let field = wmm.field(
+            40.0f64.to_radians(),
+            -105.25f64.to_radians(),
+            1650.0,
+            2025.5,
+        );
+        assert!((field.declination().to_degrees() - 7.7).abs() < 1.5);
+        assert!((field.inclination().to_degrees() - 65.5).abs() < 1.5);
+        assert!((field.intensity() - 51.7).abs() < 1.5);
+
+        // dipole dominated: pointing down in the north, up in the south
+        assert!(wmm.field(1.4, 0.0, 0.0, 2026.0).inclination() > 1.3);
+        assert!(wmm.field(-1.2, 2.0, 0.0, 2026.0).inclination() < -1.0);
+    }
+}